@@ -0,0 +1,216 @@
+//! Module dedicated to output capture configuration.
+
+use std::{
+    fmt,
+    io::Write,
+    process::ExitStatus,
+    sync::{Arc, Mutex},
+};
+
+use crate::{Chunk, Usage};
+
+/// The policy applied when a captured stream exceeds its limit.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum OverflowPolicy {
+    /// Kills the child process and fails with
+    /// [`crate::CaptureError::LimitExceeded`].
+    Error,
+
+    /// Keeps the first bytes up to the limit, and discards the rest.
+    KeepHead,
+
+    /// Keeps the last bytes up to the limit, like a ring buffer.
+    KeepTail,
+}
+
+/// The size limit of a captured stream.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct OutputLimit {
+    /// The maximum number of bytes to retain.
+    pub max_bytes: usize,
+
+    /// The policy applied when the stream exceeds the limit.
+    pub policy: OverflowPolicy,
+}
+
+impl OutputLimit {
+    /// Creates a new limit failing after `max_bytes`.
+    pub fn error(max_bytes: usize) -> Self {
+        let policy = OverflowPolicy::Error;
+        Self { max_bytes, policy }
+    }
+
+    /// Creates a new limit keeping the first `max_bytes`.
+    pub fn keep_head(max_bytes: usize) -> Self {
+        let policy = OverflowPolicy::KeepHead;
+        Self { max_bytes, policy }
+    }
+
+    /// Creates a new limit keeping the last `max_bytes`.
+    pub fn keep_tail(max_bytes: usize) -> Self {
+        let policy = OverflowPolicy::KeepTail;
+        Self { max_bytes, policy }
+    }
+}
+
+/// The way output streams are captured.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub enum CaptureMode {
+    /// Captures stdout and stderr separately.
+    #[default]
+    Separate,
+
+    /// Captures stdout and stderr into a single buffer, like `2>&1`.
+    ///
+    /// Both streams share the same pipe, so ordering is exactly the
+    /// one of the child process' writes. The merged output is
+    /// reported as stdout, and limited by the stdout limit.
+    Merged,
+
+    /// Captures stdout and stderr into a single buffer, recording
+    /// which stream each chunk comes from.
+    ///
+    /// Streams are read from distinct pipes, so ordering is the one
+    /// of reads: writes of both streams happening at the same time
+    /// may be reordered. The merged output is reported as stdout,
+    /// limited by the stdout limit, and chunks are reported in
+    /// [`CaptureOutput::chunks`].
+    Tagged,
+}
+
+/// The destination where captured output is forwarded to, while
+/// being captured.
+#[derive(Clone)]
+pub enum Tee {
+    /// Forwards stdout and stderr to the parent process' own stdout
+    /// and stderr.
+    Inherit,
+
+    /// Forwards both stdout and stderr to the given sink.
+    Sink(Arc<Mutex<dyn Write + Send>>),
+}
+
+impl Tee {
+    /// Creates a new tee forwarding output to the given sink.
+    pub fn sink(sink: impl Write + Send + 'static) -> Self {
+        Self::Sink(Arc::new(Mutex::new(sink)))
+    }
+}
+
+impl fmt::Debug for Tee {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Inherit => f.write_str("Inherit"),
+            Self::Sink(_) => f.write_str("Sink(..)"),
+        }
+    }
+}
+
+impl Eq for Tee {}
+
+impl PartialEq for Tee {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Self::Inherit, Self::Inherit) => true,
+            (Self::Sink(a), Self::Sink(b)) => Arc::ptr_eq(a, b),
+            _ => false,
+        }
+    }
+}
+
+/// The output capture configuration.
+///
+/// See [`crate::coroutines::SpawnThenCapture`].
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Capture {
+    /// The way output streams are captured.
+    pub mode: CaptureMode,
+
+    /// The destination where output is forwarded to, if any.
+    ///
+    /// Output is forwarded as soon as it is read, whether it is then
+    /// retained or not.
+    pub tee: Option<Tee>,
+
+    /// The size limit of the captured stdout, unbounded if `None`.
+    pub stdout_limit: Option<OutputLimit>,
+
+    /// The size limit of the captured stderr, unbounded if `None`.
+    pub stderr_limit: Option<OutputLimit>,
+}
+
+impl Capture {
+    /// Creates a new, unbounded capture configuration.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the way output streams are captured.
+    pub fn mode(mut self, mode: CaptureMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// Forwards output to the given destination while capturing it.
+    pub fn tee(mut self, tee: Tee) -> Self {
+        self.tee = Some(tee);
+        self
+    }
+
+    /// Limits the size of both captured streams.
+    pub fn limit(self, limit: OutputLimit) -> Self {
+        self.stdout_limit(limit).stderr_limit(limit)
+    }
+
+    /// Limits the size of the captured stdout.
+    pub fn stdout_limit(mut self, limit: OutputLimit) -> Self {
+        self.stdout_limit = Some(limit);
+        self
+    }
+
+    /// Limits the size of the captured stderr.
+    pub fn stderr_limit(mut self, limit: OutputLimit) -> Self {
+        self.stderr_limit = Some(limit);
+        self
+    }
+}
+
+/// The bytes captured from a child process' output stream.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Captured {
+    /// The retained bytes.
+    pub bytes: Vec<u8>,
+
+    /// The number of bytes discarded because of the stream limit.
+    pub discarded: u64,
+}
+
+impl Captured {
+    /// Returns `true` if bytes have been discarded.
+    pub fn is_truncated(&self) -> bool {
+        self.discarded > 0
+    }
+}
+
+/// The output of a captured child process.
+///
+/// See [`crate::Io::SpawnThenCapture`].
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct CaptureOutput {
+    /// The exit status of the child process.
+    pub status: ExitStatus,
+
+    /// The captured stdout, or both streams when they are merged.
+    pub stdout: Captured,
+
+    /// The captured stderr, empty when streams are merged.
+    pub stderr: Captured,
+
+    /// The retained chunks, in order, when streams are tagged.
+    ///
+    /// See [`CaptureMode::Tagged`].
+    pub chunks: Vec<Chunk>,
+
+    /// The timing and resource usage of the child process.
+    pub usage: Usage,
+}
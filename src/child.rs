@@ -0,0 +1,180 @@
+//! Module dedicated to the [`Child`] process handle.
+
+use std::process;
+
+#[cfg(target_os = "linux")]
+use std::os::fd::OwnedFd;
+#[cfg(any(feature = "std", feature = "tokio"))]
+use std::time::Instant;
+#[cfg(unix)]
+use std::{ptr, thread};
+
+/// The spawned child process handle.
+///
+/// This handle is returned by I/O connectors after spawning a
+/// process without waiting for it, see [`crate::coroutines::Spawn`].
+/// It can then be given back to coroutines to either wait for the
+/// child process, or to send it signals.
+#[derive(Debug)]
+pub struct Child {
+    /// The standard child process.
+    ///
+    /// Both runtimes spawn standard child processes, so that this
+    /// handle remains runtime-agnostic.
+    pub process: process::Child,
+
+    /// The ID of the process group led by the child process, if any.
+    ///
+    /// When set, signals are sent to the whole group, including any
+    /// grandchildren spawned by the child process.
+    ///
+    /// Refs: [`crate::Command::process_group`], [`crate::Command::setsid`]
+    #[cfg(unix)]
+    pub group: Option<u32>,
+
+    /// The process file descriptor referring to the child process,
+    /// if any.
+    ///
+    /// Unlike the process ID, the descriptor cannot be reused by
+    /// another process once the child exits, which makes signaling
+    /// race-free. It becomes readable once the child process exits,
+    /// so it can also be registered in event loops. It is missing
+    /// when the kernel does not support `pidfd_open(2)`.
+    #[cfg(target_os = "linux")]
+    pub pidfd: Option<OwnedFd>,
+
+    /// Whether the child process is killed when this handle is
+    /// dropped before being waited for.
+    ///
+    /// The child process is then sent `SIGKILL`, like
+    /// `tokio::process::Command::kill_on_drop`. The signal is sent to
+    /// the whole process group if the child process leads one. The
+    /// child process is reaped in the background, so that dropping
+    /// the handle never blocks.
+    ///
+    /// Refs: [`crate::Command::kill_on_drop`]
+    pub kill_on_drop: bool,
+
+    /// The instant the child process was spawned at, used by
+    /// runtimes to measure its duration.
+    #[cfg(any(feature = "std", feature = "tokio"))]
+    pub(crate) started: Instant,
+
+    /// The span of the child process, in which runtimes emit events
+    /// related to it.
+    #[cfg(feature = "tracing")]
+    pub span: tracing::Span,
+}
+
+impl Child {
+    /// Creates a new child handle from a standard child process.
+    pub fn new(process: process::Child) -> Self {
+        Self {
+            process,
+            #[cfg(unix)]
+            group: None,
+            #[cfg(target_os = "linux")]
+            pidfd: None,
+            kill_on_drop: false,
+            #[cfg(any(feature = "std", feature = "tokio"))]
+            started: Instant::now(),
+            #[cfg(feature = "tracing")]
+            span: tracing::Span::none(),
+        }
+    }
+
+    /// Sets the process group led by the child process.
+    ///
+    /// The given `group` follows [`crate::Command::process_group`]
+    /// semantics: `0` means the child process leads its own group,
+    /// while any other ID means it joined an existing group, which is
+    /// not tracked since the child process does not own it.
+    #[cfg(unix)]
+    pub fn with_group(mut self, group: Option<i32>) -> Self {
+        self.group = (group == Some(0)).then(|| self.process.id());
+        self
+    }
+
+    /// Sets the process file descriptor referring to the child
+    /// process.
+    #[cfg(target_os = "linux")]
+    pub fn with_pidfd(mut self, pidfd: Option<OwnedFd>) -> Self {
+        self.pidfd = pidfd;
+        self
+    }
+
+    /// Sets whether the child process is killed when this handle is
+    /// dropped.
+    pub fn with_kill_on_drop(mut self, kill_on_drop: bool) -> Self {
+        self.kill_on_drop = kill_on_drop;
+        self
+    }
+
+    /// Returns the OS-assigned process identifier of the child.
+    pub fn id(&self) -> u32 {
+        self.process.id()
+    }
+}
+
+impl Drop for Child {
+    fn drop(&mut self) {
+        if !self.kill_on_drop {
+            return;
+        }
+
+        // the child process already exited, and is now reaped
+        if !matches!(self.process.try_wait(), Ok(None)) {
+            return;
+        }
+
+        // errors mean that the child process already exited
+        #[cfg(all(unix, any(feature = "std", feature = "tokio")))]
+        let _ = crate::runtimes::unix::kill(self, crate::Signal::KILL);
+        #[cfg(not(all(unix, any(feature = "std", feature = "tokio"))))]
+        let _ = self.process.kill();
+
+        #[cfg(unix)]
+        {
+            // the handle is dropped, so the standard library cannot
+            // wait for the child process anymore
+            let pid = self.id() as libc::pid_t;
+            // SAFETY: a null status pointer is allowed
+            thread::spawn(move || unsafe { libc::waitpid(pid, ptr::null_mut(), 0) });
+        }
+
+        #[cfg(not(unix))]
+        let _ = self.process.wait();
+    }
+}
+
+/// The child process running in a [`crate::coroutines::JobPool`].
+///
+/// Runtimes collect the piped stdout and stderr of the child process
+/// into the job buffers while waiting for it.
+#[derive(Debug)]
+pub struct Job {
+    /// The index of the job command, as given to the pool.
+    pub index: usize,
+
+    /// The child process.
+    pub child: Child,
+
+    /// The bytes collected from the child process' stdout.
+    pub stdout: Vec<u8>,
+
+    /// The bytes collected from the child process' stderr.
+    pub stderr: Vec<u8>,
+}
+
+impl Job {
+    /// Creates a new job from the given command index and child
+    /// process.
+    pub fn new(index: usize, child: Child) -> Self {
+        Self {
+            index,
+            child,
+            stdout: Vec::new(),
+            stderr: Vec::new(),
+        }
+    }
+}
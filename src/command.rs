@@ -1,6 +1,24 @@
 //! Module dedicated to the [`Command`] builder.
 
-use std::{collections::HashMap, ffi::OsString, path::PathBuf, process::Stdio};
+use std::{
+    borrow::Cow,
+    collections::{BTreeSet, HashMap, HashSet},
+    ffi::{OsStr, OsString},
+    fmt,
+    path::PathBuf,
+    process::Stdio,
+};
+#[cfg(unix)]
+use std::{
+    collections::BTreeMap,
+    os::fd::{OwnedFd, RawFd},
+};
+
+#[cfg(target_os = "linux")]
+use crate::Signal;
+use crate::{EnvInherit, Redirect};
+#[cfg(unix)]
+use crate::{Rlimit, RlimitResource};
 
 /// The command builder.
 ///
@@ -10,7 +28,7 @@ use std::{collections::HashMap, ffi::OsString, path::PathBuf, process::Stdio};
 /// to build I/O-specific commands.
 ///
 /// Refs: [`std::process::Command`]
-#[derive(Debug, Default)]
+#[derive(Default)]
 pub struct Command {
     /// Path to the program.
     ///
@@ -27,11 +45,37 @@ pub struct Command {
     /// Refs: [`std::process::Command::get_envs`]
     pub envs: Option<HashMap<OsString, OsString>>,
 
+    /// Environment variables of the parent process inherited by the
+    /// child process, all of them by default.
+    pub env_inherit: EnvInherit,
+
+    /// Indexes of the arguments holding sensitive values, redacted
+    /// from debug output, logs, shell rendering and serialization.
+    pub sensitive_args: Option<BTreeSet<usize>>,
+
+    /// Keys of the environment variables holding sensitive values,
+    /// redacted from debug output, logs, shell rendering and
+    /// serialization.
+    pub sensitive_envs: Option<HashSet<OsString>>,
+
+    /// First argument received by the program, which defaults to
+    /// the program path.
+    ///
+    /// Refs: [`std::os::unix::process::CommandExt::arg0`]
+    #[cfg(unix)]
+    pub arg0: Option<OsString>,
+
     /// Working directory of the child process.
     ///
     /// Refs: [`std::process::Command::get_current_dir`]
     pub current_dir: Option<PathBuf>,
 
+    /// File mode creation mask of the child process.
+    ///
+    /// Refs: `umask(2)`
+    #[cfg(unix)]
+    pub umask: Option<u32>,
+
     /// Configuration for the child process's standard input (stdin)
     /// handle.
     ///
@@ -49,9 +93,98 @@ pub struct Command {
     ///
     /// Refs: [`std::process::Command::stderr`]
     pub stderr: Option<Stdio>,
+
+    /// Declarative redirection of the child process's standard input
+    /// (stdin), which takes precedence over [`Command::stdin`].
+    pub stdin_redirect: Option<Redirect>,
+
+    /// Declarative redirection of the child process's standard output
+    /// (stdout), which takes precedence over [`Command::stdout`].
+    pub stdout_redirect: Option<Redirect>,
+
+    /// Declarative redirection of the child process's standard error
+    /// (stderr), which takes precedence over [`Command::stderr`].
+    pub stderr_redirect: Option<Redirect>,
+
+    /// Whether the child process should be killed when its handle is
+    /// dropped.
+    ///
+    /// Refs: [`crate::Child::kill_on_drop`],
+    /// `tokio::process::Command::kill_on_drop`
+    pub kill_on_drop: bool,
+
+    /// User ID the child process switches to before executing the
+    /// program.
+    ///
+    /// Refs: [`std::os::unix::process::CommandExt::uid`]
+    #[cfg(unix)]
+    pub uid: Option<u32>,
+
+    /// Group ID the child process switches to before executing the
+    /// program.
+    ///
+    /// Refs: [`std::os::unix::process::CommandExt::gid`]
+    #[cfg(unix)]
+    pub gid: Option<u32>,
+
+    /// Supplementary group IDs of the child process.
+    ///
+    /// Refs: [`std::os::unix::process::CommandExt::groups`]
+    #[cfg(unix)]
+    pub groups: Option<Vec<u32>>,
+
+    /// Process group ID of the child process.
+    ///
+    /// A value of `0` puts the child process in a new process group
+    /// whose ID matches the child process ID.
+    ///
+    /// Refs: [`std::os::unix::process::CommandExt::process_group`]
+    #[cfg(unix)]
+    pub process_group: Option<i32>,
+
+    /// Whether the child process should run in a new session.
+    ///
+    /// The child process then leads a new process group, detached
+    /// from any controlling terminal. This option takes precedence
+    /// over [`Command::process_group`].
+    ///
+    /// Refs: [`std::os::unix::process::CommandExt::setsid`]
+    #[cfg(unix)]
+    pub setsid: bool,
+
+    /// Resource limits applied to the child process, before executing
+    /// the program.
+    ///
+    /// Refs: `setrlimit(2)`
+    #[cfg(unix)]
+    pub rlimits: Option<Vec<Rlimit>>,
+
+    /// Extra file descriptors inherited by the child process, indexed
+    /// by their number in the child process.
+    ///
+    /// Like std{in,out,err}, these descriptors are neither cloned nor
+    /// compared nor serialized.
+    #[cfg(unix)]
+    pub fds: Option<BTreeMap<RawFd, OwnedFd>>,
+
+    /// Signal sent to the child process when its parent exits.
+    ///
+    /// Refs: `prctl(2)` `PR_SET_PDEATHSIG`
+    #[cfg(target_os = "linux")]
+    pub parent_death_signal: Option<Signal>,
 }
 
 impl Command {
+    /// The value replacing sensitive arguments and environment
+    /// variables in debug output, logs, shell rendering and
+    /// serialization.
+    pub const REDACTED: &'static str = "<redacted>";
+
+    /// The variables set by [`Command::env_minimal`].
+    #[cfg(unix)]
+    pub const MINIMAL_ENV: [(&'static str, &'static str); 2] =
+        [("PATH", "/usr/local/bin:/usr/bin:/bin"), ("LC_ALL", "C")];
+
     /// Constructs a new [`Command`] for launching the program at path
     /// `program`. This is just a builder, it does not launch any
     /// program on its own. Only I/O connectors do spawn processes.
@@ -61,11 +194,38 @@ impl Command {
         Self {
             program: program.into(),
             args: None,
+            #[cfg(unix)]
+            arg0: None,
             envs: None,
+            env_inherit: EnvInherit::All,
+            sensitive_args: None,
+            sensitive_envs: None,
             current_dir: None,
+            #[cfg(unix)]
+            umask: None,
             stdin: None,
             stdout: None,
             stderr: None,
+            stdin_redirect: None,
+            stdout_redirect: None,
+            stderr_redirect: None,
+            kill_on_drop: false,
+            #[cfg(unix)]
+            uid: None,
+            #[cfg(unix)]
+            gid: None,
+            #[cfg(unix)]
+            groups: None,
+            #[cfg(unix)]
+            process_group: None,
+            #[cfg(unix)]
+            setsid: false,
+            #[cfg(unix)]
+            rlimits: None,
+            #[cfg(unix)]
+            fds: None,
+            #[cfg(target_os = "linux")]
+            parent_death_signal: None,
         }
     }
 
@@ -84,6 +244,21 @@ impl Command {
         self
     }
 
+    /// Adds an argument holding a sensitive value, like a password,
+    /// to pass to the program.
+    ///
+    /// The value is passed as is to the program, but is replaced by
+    /// [`Command::REDACTED`] in debug output, logs, shell rendering
+    /// and serialization.
+    pub fn sensitive_arg<S: Into<OsString>>(&mut self, arg: S) -> &mut Self {
+        self.arg(arg);
+        let index = self.args.as_ref().map_or(0, Vec::len) - 1;
+        self.sensitive_args
+            .get_or_insert_with(Default::default)
+            .insert(index);
+        self
+    }
+
     /// Adds multiple arguments to pass to the program.
     ///
     /// Refs: [`std::process::Command::args`]
@@ -98,6 +273,16 @@ impl Command {
         self
     }
 
+    /// Sets the first argument received by the program, instead of
+    /// the program path.
+    ///
+    /// Refs: [`std::os::unix::process::CommandExt::arg0`]
+    #[cfg(unix)]
+    pub fn arg0<S: Into<OsString>>(&mut self, arg: S) -> &mut Self {
+        self.arg0 = Some(arg.into());
+        self
+    }
+
     /// Inserts or updates an explicit environment variable mapping.
     ///
     /// Refs: [`std::process::Command::env`]
@@ -133,8 +318,32 @@ impl Command {
         self
     }
 
-    /// Removes an explicitly set environment variable and prevents
-    /// inheriting it from a parent process.
+    /// Inserts or updates an explicit environment variable mapping
+    /// holding a sensitive value, like a token.
+    ///
+    /// The value is passed as is to the child process, but is
+    /// replaced by [`Command::REDACTED`] in debug output, logs, shell
+    /// rendering and serialization.
+    pub fn sensitive_env<K, V>(&mut self, key: K, val: V) -> &mut Self
+    where
+        K: Into<OsString>,
+        V: Into<OsString>,
+    {
+        let key = key.into();
+        self.env(key.clone(), val);
+        self.sensitive_envs
+            .get_or_insert_with(Default::default)
+            .insert(key);
+        self
+    }
+
+    /// Removes an explicitly set environment variable.
+    ///
+    /// The variable is still inherited from the parent process when
+    /// the [`EnvInherit`] policy allows it, which is the case by
+    /// default. To prevent inheriting it, use a policy leaving it out,
+    /// like [`EnvInherit::None`] or an [`EnvInherit::Allowlist`] not
+    /// listing it (see [`Command::env_inherit`]).
     ///
     /// Refs: [`std::process::Command::env_remove`]
     pub fn env_remove<K: Into<OsString>>(&mut self, key: K) -> &mut Self {
@@ -149,13 +358,54 @@ impl Command {
     ///
     /// Refs: [`std::process::Command::env_clear`]
     pub fn env_clear(&mut self) -> &mut Self {
-        if let Some(envs) = &mut self.envs {
-            envs.clear();
-        }
         self.envs = None;
+        self.env_inherit = EnvInherit::None;
         self
     }
 
+    /// Sets which environment variables of the parent process the
+    /// child process inherits.
+    pub fn env_inherit(&mut self, policy: EnvInherit) -> &mut Self {
+        self.env_inherit = policy;
+        self
+    }
+
+    /// Replaces the environment of the child process by a minimal,
+    /// reproducible one: nothing is inherited from the parent
+    /// process, except variables explicitly set afterwards, and
+    /// [`Command::MINIMAL_ENV`] variables are set.
+    #[cfg(unix)]
+    pub fn env_minimal(&mut self) -> &mut Self {
+        self.env_clear().envs(Self::MINIMAL_ENV)
+    }
+
+    /// Returns the arguments, with sensitive ones redacted.
+    pub fn get_redacted_args(&self) -> Option<Vec<&OsStr>> {
+        let args = self.args.as_ref()?;
+        let sensitive = |i| matches!(&self.sensitive_args, Some(s) if s.contains(&i));
+
+        let args = args.iter().enumerate().map(|(i, arg)| match sensitive(i) {
+            true => OsStr::new(Self::REDACTED),
+            false => arg.as_os_str(),
+        });
+
+        Some(args.collect())
+    }
+
+    /// Returns the explicit environment variables, with sensitive
+    /// values redacted.
+    pub fn get_redacted_envs(&self) -> Option<HashMap<&OsStr, &OsStr>> {
+        let envs = self.envs.as_ref()?;
+        let sensitive = |key| matches!(&self.sensitive_envs, Some(s) if s.contains(key));
+
+        let envs = envs.iter().map(|(key, val)| match sensitive(key) {
+            true => (key.as_os_str(), OsStr::new(Self::REDACTED)),
+            false => (key.as_os_str(), val.as_os_str()),
+        });
+
+        Some(envs.collect())
+    }
+
     /// Sets the working directory for the child process.
     ///
     /// Refs: [`std::process::Command::current_dir`]
@@ -164,12 +414,22 @@ impl Command {
         self
     }
 
+    /// Sets the file mode creation mask of the child process.
+    ///
+    /// Refs: `umask(2)`
+    #[cfg(unix)]
+    pub fn umask(&mut self, mask: u32) -> &mut Command {
+        self.umask = Some(mask);
+        self
+    }
+
     /// Configuration for the child process's standard input (stdin)
     /// handle.
     ///
     /// Refs: [`std::process::Command::stdin`]
     pub fn stdin<T: Into<Stdio>>(&mut self, cfg: T) -> &mut Command {
         self.stdin = Some(cfg.into());
+        self.stdin_redirect = None;
         self
     }
 
@@ -179,6 +439,7 @@ impl Command {
     /// Refs: [`std::process::Command::stdout`]
     pub fn stdout<T: Into<Stdio>>(&mut self, cfg: T) -> &mut Command {
         self.stdout = Some(cfg.into());
+        self.stdout_redirect = None;
         self
     }
 
@@ -188,10 +449,206 @@ impl Command {
     /// Refs: [`std::process::Command::stderr`]
     pub fn stderr<T: Into<Stdio>>(&mut self, cfg: T) -> &mut Command {
         self.stderr = Some(cfg.into());
+        self.stderr_redirect = None;
         self
     }
+
+    /// Redirects the child process's standard input (stdin).
+    ///
+    /// Unlike [`Command::stdin`], the redirection is opened by I/O
+    /// connectors at spawn time.
+    pub fn stdin_redirect(&mut self, redirect: Redirect) -> &mut Command {
+        self.stdin_redirect = Some(redirect);
+        self.stdin = None;
+        self
+    }
+
+    /// Redirects the child process's standard output (stdout).
+    ///
+    /// Unlike [`Command::stdout`], the redirection is opened by I/O
+    /// connectors at spawn time.
+    pub fn stdout_redirect(&mut self, redirect: Redirect) -> &mut Command {
+        self.stdout_redirect = Some(redirect);
+        self.stdout = None;
+        self
+    }
+
+    /// Redirects the child process's standard error (stderr).
+    ///
+    /// Unlike [`Command::stderr`], the redirection is opened by I/O
+    /// connectors at spawn time.
+    pub fn stderr_redirect(&mut self, redirect: Redirect) -> &mut Command {
+        self.stderr_redirect = Some(redirect);
+        self.stderr = None;
+        self
+    }
+
+    /// Redirects the child process's standard input (stdin) from the
+    /// file at the given path.
+    pub fn stdin_file<P: Into<PathBuf>>(&mut self, path: P) -> &mut Command {
+        self.stdin_redirect(Redirect::File(path.into()))
+    }
+
+    /// Redirects the child process's standard output (stdout) to the
+    /// file at the given path, either truncated or appended to.
+    pub fn stdout_file<P: Into<PathBuf>>(&mut self, path: P, append: bool) -> &mut Command {
+        self.stdout_redirect(match append {
+            true => Redirect::Append(path.into()),
+            false => Redirect::File(path.into()),
+        })
+    }
+
+    /// Redirects the child process's standard error (stderr) to the
+    /// file at the given path, either truncated or appended to.
+    pub fn stderr_file<P: Into<PathBuf>>(&mut self, path: P, append: bool) -> &mut Command {
+        self.stderr_redirect(match append {
+            true => Redirect::Append(path.into()),
+            false => Redirect::File(path.into()),
+        })
+    }
+
+    /// Sets the child process's user ID.
+    ///
+    /// Refs: [`std::os::unix::process::CommandExt::uid`]
+    #[cfg(unix)]
+    pub fn uid(&mut self, id: u32) -> &mut Command {
+        self.uid = Some(id);
+        self
+    }
+
+    /// Sets the child process's group ID.
+    ///
+    /// Refs: [`std::os::unix::process::CommandExt::gid`]
+    #[cfg(unix)]
+    pub fn gid(&mut self, id: u32) -> &mut Command {
+        self.gid = Some(id);
+        self
+    }
+
+    /// Sets the supplementary group IDs for the child process.
+    ///
+    /// Without explicit groups, a child process spawned by root then
+    /// switching to another [`uid`](Command::uid) drops all its
+    /// supplementary groups.
+    ///
+    /// Refs: [`std::os::unix::process::CommandExt::groups`]
+    #[cfg(unix)]
+    pub fn groups(&mut self, groups: &[u32]) -> &mut Command {
+        self.groups = Some(groups.to_vec());
+        self
+    }
+
+    /// Sets the process group ID of the child process.
+    ///
+    /// A `pgroup` of `0` makes the child process lead a new group,
+    /// which is then signaled as a whole. Any other ID makes it join
+    /// an existing group, and only the child process is signaled.
+    ///
+    /// Refs: [`std::os::unix::process::CommandExt::process_group`]
+    #[cfg(unix)]
+    pub fn process_group(&mut self, pgroup: i32) -> &mut Command {
+        self.process_group = Some(pgroup);
+        self
+    }
+
+    /// Sets whether the child process should run in a new session.
+    ///
+    /// Refs: [`std::os::unix::process::CommandExt::setsid`]
+    #[cfg(unix)]
+    pub fn setsid(&mut self, setsid: bool) -> &mut Command {
+        self.setsid = setsid;
+        self
+    }
+
+    /// Adds a resource limit to apply to the child process.
+    ///
+    /// Limits set to `None` keep their current value, see [`Rlimit`].
+    /// Adding a limit for an already limited resource replaces the
+    /// previous one.
+    ///
+    /// Refs: `setrlimit(2)`
+    #[cfg(unix)]
+    pub fn rlimit(
+        &mut self,
+        resource: RlimitResource,
+        soft: Option<u64>,
+        hard: Option<u64>,
+    ) -> &mut Command {
+        let rlimit = Rlimit::new(resource, soft, hard);
+        let rlimits = self.rlimits.get_or_insert_with(Vec::new);
+
+        match rlimits.iter_mut().find(|r| r.resource == resource) {
+            Some(prev) => *prev = rlimit,
+            None => rlimits.push(rlimit),
+        }
+
+        self
+    }
+
+    /// Makes the child process inherit the given `source` descriptor
+    /// as `child_fd`.
+    ///
+    /// The source can be any parent file descriptor, like an opened
+    /// file or one end of a pipe. It is closed in the parent process
+    /// once the child process is spawned. Mapping the same `child_fd`
+    /// twice replaces the previous source.
+    ///
+    /// ```rust,ignore
+    /// // gpg --passphrase-fd 3
+    /// command.arg("--passphrase-fd").arg("3");
+    /// command.inherit_fd(3, passphrase_reader);
+    /// ```
+    #[cfg(unix)]
+    pub fn inherit_fd<F: Into<OwnedFd>>(&mut self, child_fd: RawFd, source: F) -> &mut Command {
+        let fds = self.fds.get_or_insert_with(BTreeMap::new);
+        fds.insert(child_fd, source.into());
+        self
+    }
+
+    /// Sets the signal sent to the child process when its parent
+    /// exits, so that it does not outlive a crashed or killed parent.
+    ///
+    /// The signal is actually sent when the thread that spawned the
+    /// child process exits, which matters for runtimes spawning from
+    /// short-lived threads. It is also not sent to grandchildren.
+    ///
+    /// Refs: `prctl(2)` `PR_SET_PDEATHSIG`
+    #[cfg(target_os = "linux")]
+    pub fn parent_death_signal(&mut self, signal: Signal) -> &mut Command {
+        self.parent_death_signal = Some(signal);
+        self
+    }
+
+    /// Sets whether the child process should be killed when its
+    /// handle is dropped before being waited for.
+    ///
+    /// Refs: [`crate::Child::kill_on_drop`],
+    /// `tokio::process::Command::kill_on_drop`
+    pub fn kill_on_drop(&mut self, kill_on_drop: bool) -> &mut Command {
+        self.kill_on_drop = kill_on_drop;
+        self
+    }
+
+    /// Returns the process group the child process will belong to,
+    /// following [`std::os::unix::process::CommandExt::process_group`]
+    /// semantics.
+    #[cfg(unix)]
+    pub fn get_process_group(&self) -> Option<i32> {
+        if self.setsid {
+            Some(0)
+        } else {
+            self.process_group
+        }
+    }
 }
 
+/// Clones the command builder, except its std{in,out,err}
+/// configurations and its inherited file descriptors, which cannot be
+/// cloned.
+///
+/// Coroutines spawning a command several times, like
+/// [`crate::coroutines::Retry`], therefore take a function building
+/// it instead of cloning it, so that each spawn gets its own handles.
 impl Clone for Command {
     fn clone(&self) -> Self {
         let mut command = Command::new(&self.program);
@@ -202,16 +659,46 @@ impl Clone for Command {
             }
         }
 
+        #[cfg(unix)]
+        {
+            command.arg0 = self.arg0.clone();
+            command.umask = self.umask;
+        }
+
         if let Some(envs) = self.envs.as_ref() {
             for (key, val) in envs {
                 command.env(key, val);
             }
         }
 
+        command.env_inherit = self.env_inherit.clone();
+        command.sensitive_args = self.sensitive_args.clone();
+        command.sensitive_envs = self.sensitive_envs.clone();
+
         if let Some(dir) = self.current_dir.as_ref() {
             command.current_dir(dir);
         }
 
+        command.stdin_redirect = self.stdin_redirect.clone();
+        command.stdout_redirect = self.stdout_redirect.clone();
+        command.stderr_redirect = self.stderr_redirect.clone();
+        command.kill_on_drop = self.kill_on_drop;
+
+        #[cfg(unix)]
+        {
+            command.uid = self.uid;
+            command.gid = self.gid;
+            command.groups = self.groups.clone();
+            command.process_group = self.process_group;
+            command.setsid = self.setsid;
+            command.rlimits = self.rlimits.clone();
+        }
+
+        #[cfg(target_os = "linux")]
+        {
+            command.parent_death_signal = self.parent_death_signal;
+        }
+
         command
     }
 }
@@ -228,10 +715,244 @@ impl PartialEq for Command {
             return false;
         }
 
-        if self.current_dir != other.current_dir {
+        #[cfg(unix)]
+        if self.arg0 != other.arg0 || self.umask != other.umask {
+            return false;
+        }
+
+        if self.env_inherit != other.env_inherit || self.current_dir != other.current_dir {
+            return false;
+        }
+
+        if self.sensitive_args != other.sensitive_args
+            || self.sensitive_envs != other.sensitive_envs
+        {
+            return false;
+        }
+
+        if self.stdin_redirect != other.stdin_redirect
+            || self.stdout_redirect != other.stdout_redirect
+            || self.stderr_redirect != other.stderr_redirect
+        {
+            return false;
+        }
+
+        if self.kill_on_drop != other.kill_on_drop {
+            return false;
+        }
+
+        #[cfg(unix)]
+        if self.uid != other.uid || self.gid != other.gid || self.groups != other.groups {
+            return false;
+        }
+
+        #[cfg(unix)]
+        if self.process_group != other.process_group || self.setsid != other.setsid {
+            return false;
+        }
+
+        #[cfg(unix)]
+        if self.rlimits != other.rlimits {
+            return false;
+        }
+
+        #[cfg(target_os = "linux")]
+        if self.parent_death_signal != other.parent_death_signal {
             return false;
         }
 
         true
     }
 }
+
+/// Formats the command, with sensitive arguments and environment
+/// variables redacted.
+impl fmt::Debug for Command {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut debug = f.debug_struct("Command");
+
+        debug
+            .field("program", &self.program)
+            .field("args", &self.get_redacted_args())
+            .field("envs", &self.get_redacted_envs())
+            .field("env_inherit", &self.env_inherit)
+            .field("sensitive_args", &self.sensitive_args)
+            .field("sensitive_envs", &self.sensitive_envs);
+
+        #[cfg(unix)]
+        debug.field("arg0", &self.arg0);
+        debug.field("current_dir", &self.current_dir);
+        #[cfg(unix)]
+        debug.field("umask", &self.umask);
+
+        debug
+            .field("stdin", &self.stdin)
+            .field("stdout", &self.stdout)
+            .field("stderr", &self.stderr)
+            .field("stdin_redirect", &self.stdin_redirect)
+            .field("stdout_redirect", &self.stdout_redirect)
+            .field("stderr_redirect", &self.stderr_redirect)
+            .field("kill_on_drop", &self.kill_on_drop);
+
+        #[cfg(unix)]
+        debug
+            .field("uid", &self.uid)
+            .field("gid", &self.gid)
+            .field("groups", &self.groups)
+            .field("process_group", &self.process_group)
+            .field("setsid", &self.setsid)
+            .field("rlimits", &self.rlimits)
+            .field("fds", &self.fds);
+        #[cfg(target_os = "linux")]
+        debug.field("parent_death_signal", &self.parent_death_signal);
+
+        debug.finish()
+    }
+}
+
+/// Renders the command as a shell command line, for display purpose
+/// only (logs, dry runs).
+///
+/// Arguments are quoted when needed, and options that have a shell
+/// equivalent are rendered as such:
+///
+/// ```text
+/// cd /tmp && KEY=val exec -a name program 'arg 1' <in >>out 3<&?
+/// ```
+///
+/// Inherited file descriptors are rendered by their number in the
+/// child process only, since their number in the parent process
+/// means nothing to a reader.
+///
+/// Options without shell equivalent (environment inheritance,
+/// std{in,out,err} handles, credentials, limits etc) are not
+/// rendered.
+impl fmt::Display for Command {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(dir) = &self.current_dir {
+            write!(f, "cd {} && ", quote(dir.as_os_str()))?;
+        }
+
+        if let Some(envs) = self.get_redacted_envs() {
+            let mut envs: Vec<_> = envs.into_iter().collect();
+            envs.sort();
+
+            for (key, val) in envs {
+                write!(f, "{}={} ", key.to_string_lossy(), quote(val))?;
+            }
+        }
+
+        #[cfg(unix)]
+        if let Some(arg0) = &self.arg0 {
+            write!(f, "exec -a {} ", quote(arg0))?;
+        }
+
+        write!(f, "{}", quote(&self.program))?;
+
+        if let Some(args) = self.get_redacted_args() {
+            for arg in args {
+                write!(f, " {}", quote(arg))?;
+            }
+        }
+
+        let redirects = [
+            ("<", &self.stdin_redirect),
+            (">", &self.stdout_redirect),
+            ("2>", &self.stderr_redirect),
+        ];
+
+        for (op, redirect) in redirects {
+            match redirect {
+                None => (),
+                Some(Redirect::Null) => write!(f, " {op}/dev/null")?,
+                Some(Redirect::File(path)) => write!(f, " {op}{}", quote(path.as_os_str()))?,
+                Some(Redirect::Append(path)) if op == "<" => {
+                    write!(f, " {op}{}", quote(path.as_os_str()))?
+                }
+                Some(Redirect::Append(path)) => write!(f, " {op}>{}", quote(path.as_os_str()))?,
+            }
+        }
+
+        #[cfg(unix)]
+        if let Some(fds) = &self.fds {
+            for child_fd in fds.keys() {
+                write!(f, " {child_fd}<&?")?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Quotes the given string for a POSIX shell, if needed.
+fn quote(s: &OsStr) -> Cow<'_, str> {
+    let s = s.to_string_lossy();
+
+    let safe = |c: char| c.is_ascii_alphanumeric() || "-_./:=@%+,".contains(c);
+
+    if !s.is_empty() && s.chars().all(safe) {
+        return s;
+    }
+
+    Cow::Owned(format!("'{}'", s.replace('\'', "'\\''")))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Command, Redirect};
+
+    #[test]
+    fn display() {
+        let mut command = Command::new("program");
+        command.arg("arg1").arg("arg 2").arg("it's").arg("");
+        command.env("KEY", "val ue").current_dir("/tmp");
+
+        let expected = "cd /tmp && KEY='val ue' program arg1 'arg 2' 'it'\\''s' ''";
+        assert_eq!(expected, command.to_string());
+
+        let mut command = Command::new("program");
+        command.stdin_file("in put").stdout_file("out", true);
+        command.stderr_redirect(Redirect::Null);
+
+        let expected = "program <'in put' >>out 2>/dev/null";
+        assert_eq!(expected, command.to_string());
+    }
+
+    #[test]
+    fn redacted() {
+        let mut command = Command::new("program");
+        command
+            .arg("--user")
+            .arg("me")
+            .arg("--pass")
+            .sensitive_arg("secret");
+        command.sensitive_env("TOKEN", "secret").env("KEY", "val");
+
+        let expected = "KEY=val TOKEN='<redacted>' program --user me --pass '<redacted>'";
+        assert_eq!(expected, command.to_string());
+
+        let debug = format!("{command:?}");
+        assert!(debug.contains("<redacted>"));
+        assert!(!debug.contains("secret"));
+
+        let args = command.args.as_ref().unwrap();
+        assert_eq!("secret", args[3]);
+        let envs = command.envs.as_ref().unwrap();
+        assert_eq!("secret", envs[std::ffi::OsStr::new("TOKEN")]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn display_unix() {
+        use std::fs::File;
+
+        let file = File::open("/dev/null").unwrap();
+
+        let mut command = Command::new("/usr/bin/gpg");
+        command.arg0("gpg").arg("--passphrase-fd").arg("3");
+        command.inherit_fd(3, file);
+
+        let expected = "exec -a gpg /usr/bin/gpg --passphrase-fd 3 3<&?";
+        assert_eq!(expected, command.to_string());
+    }
+}
@@ -0,0 +1,198 @@
+//! Module dedicated to the I/O-free [`Expect`] coroutine.
+
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+use log::debug;
+
+use crate::{Child, Io, ReadOutput};
+
+/// The step of an [`Expect`] script.
+///
+/// A step waits for a pattern to appear on the child process' stdout
+/// or stderr, then sends bytes to its stdin.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ExpectStep {
+    /// The bytes to wait for.
+    ///
+    /// An empty pattern matches immediately.
+    pub pattern: Vec<u8>,
+
+    /// The bytes to send once the pattern is found.
+    ///
+    /// Nothing is sent if empty.
+    pub send: Vec<u8>,
+
+    /// The maximum amount of time to wait for the pattern.
+    ///
+    /// The step waits forever if `None`.
+    pub timeout: Option<Duration>,
+}
+
+impl ExpectStep {
+    /// Creates a new step waiting for the given pattern then sending
+    /// the given bytes, without timeout.
+    pub fn new(pattern: impl Into<Vec<u8>>, send: impl Into<Vec<u8>>) -> Self {
+        Self {
+            pattern: pattern.into(),
+            send: send.into(),
+            timeout: None,
+        }
+    }
+
+    /// Sets the maximum amount of time to wait for the pattern.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+}
+
+/// The output of the [`Expect`] coroutine.
+///
+/// The child process is given back in all cases, so that it can be
+/// waited or killed.
+#[derive(Debug)]
+pub enum ExpectOutput {
+    /// All the steps of the script succeeded.
+    Done(Child),
+
+    /// The pattern of the step at the given index was not found
+    /// before its timeout.
+    Timeout(Child, usize),
+
+    /// The child process closed its output streams before the
+    /// pattern of the step at the given index was found.
+    Eof(Child, usize),
+}
+
+/// The I/O-free coroutine for driving a spawned child process through
+/// a script of expectations.
+///
+/// This coroutine should be used to automate programs prompting for
+/// input. For each [`ExpectStep`], it reads the child process' stdout
+/// and stderr until the step pattern is found, then writes the step
+/// bytes to its stdin.
+///
+/// The child process is usually obtained from [`super::Spawn`], and
+/// needs piped std{in,out,err}.
+#[derive(Debug)]
+pub struct Expect {
+    child: Option<Child>,
+    steps: VecDeque<ExpectStep>,
+    step: usize,
+    deadline: Option<Instant>,
+    buffer: Vec<u8>,
+}
+
+impl Expect {
+    /// Creates a new coroutine from the given child process and the
+    /// steps of the script.
+    pub fn new(child: Child, steps: impl IntoIterator<Item = ExpectStep>) -> Self {
+        let steps: VecDeque<_> = steps.into_iter().collect();
+        debug!(
+            "prepare child {} to expect {} steps",
+            child.id(),
+            steps.len()
+        );
+
+        Self {
+            child: Some(child),
+            steps,
+            step: 0,
+            deadline: None,
+            buffer: Vec::new(),
+        }
+    }
+
+    /// Makes the coroutine progress.
+    pub fn resume(&mut self, input: Option<Io>) -> Result<ExpectOutput, Io> {
+        let child = match input {
+            None => match self.child.take() {
+                Some(child) => child,
+                None => return Err(Io::UnavailableInput),
+            },
+            Some(Io::Read(Ok((child, output)))) => match output {
+                ReadOutput::Chunk(chunk) => {
+                    debug!("read {} bytes from {:?}", chunk.bytes.len(), chunk.stream);
+                    self.buffer.extend(chunk.bytes);
+                    child
+                }
+                ReadOutput::Eof => {
+                    debug!("reached end of file at step {}", self.step);
+                    return Ok(ExpectOutput::Eof(child, self.step));
+                }
+                ReadOutput::Timeout => {
+                    debug!("reached timeout at step {}", self.step);
+                    return Ok(ExpectOutput::Timeout(child, self.step));
+                }
+            },
+            Some(Io::Write(Ok(child))) => {
+                debug!("successfully sent bytes of step {}", self.step);
+                self.next_step();
+                child
+            }
+            Some(Io::Read(Err(input))) => return Err(Io::Read(Err(input))),
+            Some(Io::Write(Err(input))) => return Err(Io::Write(Err(input))),
+            Some(input) => return Err(Io::UnexpectedInput(Box::new(input))),
+        };
+
+        loop {
+            let Some(step) = self.steps.front() else {
+                debug!("successfully ran expect script");
+                return Ok(ExpectOutput::Done(child));
+            };
+
+            if let Some(end) = find(&self.buffer, &step.pattern) {
+                debug!("found pattern of step {}", self.step);
+                self.buffer.drain(..end);
+
+                if step.send.is_empty() {
+                    self.next_step();
+                    continue;
+                }
+
+                debug!("need to send bytes of step {}", self.step);
+                return Err(Io::Write(Err((child, step.send.clone()))));
+            }
+
+            let now = Instant::now();
+            let deadline = match step.timeout {
+                Some(timeout) => Some(*self.deadline.get_or_insert(now + timeout)),
+                None => None,
+            };
+
+            let timeout = match deadline {
+                Some(deadline) if deadline <= now => {
+                    debug!("reached timeout at step {}", self.step);
+                    return Ok(ExpectOutput::Timeout(child, self.step));
+                }
+                Some(deadline) => Some(deadline - now),
+                None => None,
+            };
+
+            debug!("need to read output for step {}", self.step);
+            return Err(Io::Read(Err((child, timeout))));
+        }
+    }
+
+    fn next_step(&mut self) {
+        self.steps.pop_front();
+        self.step += 1;
+        self.deadline = None;
+    }
+}
+
+/// Returns the index following the first occurrence of the given
+/// pattern.
+fn find(haystack: &[u8], pattern: &[u8]) -> Option<usize> {
+    if pattern.is_empty() {
+        return Some(0);
+    }
+
+    haystack
+        .windows(pattern.len())
+        .position(|window| window == pattern)
+        .map(|i| i + pattern.len())
+}
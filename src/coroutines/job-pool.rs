@@ -0,0 +1,143 @@
+//! Module dedicated to the I/O-free [`JobPool`] coroutine.
+
+use std::{collections::VecDeque, mem, process::Stdio};
+
+use log::debug;
+
+use crate::{Command, Io, Job, Output};
+
+/// The I/O-free coroutine for running many commands concurrently,
+/// with bounded parallelism.
+///
+/// This coroutine spawns commands in order, keeping at most `max`
+/// of them running at once, and outputs each result along with the
+/// index of its command as soon as it completes. Once all commands
+/// completed, it outputs `None`.
+///
+/// Like [`super::SpawnThenWaitWithOutput`], stdout and stderr are
+/// captured unless configured otherwise, and stdin defaults to
+/// `/dev/null`.
+///
+/// Running jobs are killed then reaped when the pool is dropped
+/// before completion, for example after a failed spawn.
+///
+/// ```rust,ignore
+/// let mut arg = None;
+/// let mut pool = JobPool::new(commands, 4);
+///
+/// while let Some((index, output)) = loop {
+///     match pool.resume(arg.take()) {
+///         Ok(result) => break result,
+///         Err(io) => arg = Some(handle(io).unwrap()),
+///     }
+/// } {
+///     println!("command {index}: {}", output.status);
+/// }
+/// ```
+#[derive(Debug)]
+pub struct JobPool {
+    commands: VecDeque<(usize, Command)>,
+    running: Vec<Job>,
+    spawning: Option<usize>,
+    max: usize,
+}
+
+impl JobPool {
+    /// Creates a new coroutine from the given commands, running at
+    /// most `max` of them at once.
+    pub fn new(commands: impl IntoIterator<Item = Command>, max: usize) -> Self {
+        let commands: VecDeque<_> = commands
+            .into_iter()
+            .map(|mut command| {
+                if command.stdin.is_none() {
+                    command.stdin(Stdio::null());
+                }
+
+                if command.stdout.is_none() {
+                    command.stdout(Stdio::piped());
+                }
+
+                if command.stderr.is_none() {
+                    command.stderr(Stdio::piped());
+                }
+
+                command
+            })
+            .enumerate()
+            .collect();
+
+        debug!(
+            "prepare {} commands to run in a pool of {max}",
+            commands.len()
+        );
+
+        Self {
+            commands,
+            running: Vec::new(),
+            spawning: None,
+            max: max.max(1),
+        }
+    }
+
+    /// Makes the coroutine progress.
+    pub fn resume(&mut self, input: Option<Io>) -> Result<Option<(usize, Output)>, Io> {
+        match input {
+            None => (),
+            Some(Io::Spawn(Ok(mut child))) => {
+                let Some(index) = self.spawning.take() else {
+                    return Err(Io::UnexpectedInput(Box::new(Io::Spawn(Ok(child)))));
+                };
+
+                debug!("spawned job {index}: {child:?}");
+                // closes stdin, like std::process::Command::output
+                child.process.stdin.take();
+                self.running.push(Job::new(index, child));
+            }
+            Some(Io::WaitAny(Ok((jobs, index, output)))) => {
+                debug!("job {index} completed with {}", output.status);
+                self.running = jobs;
+                return Ok(Some((index, output)));
+            }
+            Some(Io::Spawn(Err(command))) => return Err(Io::Spawn(Err(command))),
+            Some(Io::WaitAny(Err(jobs))) => return Err(Io::WaitAny(Err(jobs))),
+            Some(input) => return Err(Io::UnexpectedInput(Box::new(input))),
+        }
+
+        if self.running.len() < self.max {
+            if let Some((index, command)) = self.commands.pop_front() {
+                debug!("need to spawn job {index}");
+                self.spawning = Some(index);
+                return Err(Io::Spawn(Err(command)));
+            }
+        }
+
+        if self.running.is_empty() {
+            debug!("all jobs completed");
+            return Ok(None);
+        }
+
+        debug!("need to wait for any of {} jobs", self.running.len());
+        Err(Io::WaitAny(Err(mem::take(&mut self.running))))
+    }
+}
+
+impl Drop for JobPool {
+    fn drop(&mut self) {
+        if self.running.is_empty() {
+            return;
+        }
+
+        debug!("abort {} running jobs", self.running.len());
+        let jobs = mem::take(&mut self.running);
+
+        #[cfg(all(unix, any(feature = "std", feature = "tokio")))]
+        crate::runtimes::unix::abort_jobs(jobs);
+
+        #[cfg(not(all(unix, any(feature = "std", feature = "tokio"))))]
+        for mut job in jobs {
+            // errors mean that the child process already exited
+            let _ = job.child.process.kill();
+            let _ = job.child.process.wait();
+        }
+    }
+}
@@ -0,0 +1,55 @@
+//! Module dedicated to the I/O-free [`Kill`] coroutine.
+
+use log::debug;
+
+use crate::{Child, Io, Signal};
+
+/// The I/O-free coroutine for sending a signal to a spawned child
+/// process.
+///
+/// If the child process leads a process group (see
+/// [`crate::Command::process_group`] and [`crate::Command::setsid`]),
+/// the signal is sent to the whole group, so that grandchildren are
+/// reached as well.
+///
+/// The child process is given back once the signal is sent, so that
+/// it can still be waited using [`super::Wait`].
+#[derive(Debug)]
+pub struct Kill {
+    input: Option<(Child, Signal)>,
+}
+
+impl Kill {
+    /// Creates a new coroutine from the given child process and the
+    /// signal to send.
+    pub fn new(child: Child, signal: Signal) -> Self {
+        debug!("prepare {signal} to be sent to child {}", child.id());
+        let input = Some((child, signal));
+        Self { input }
+    }
+
+    /// Makes the coroutine progress.
+    pub fn resume(&mut self, input: Option<Io>) -> Result<Child, Io> {
+        let Some(input) = input else {
+            return Err(match self.input.take() {
+                Some(input) => Io::Kill(Err(input)),
+                None => Io::UnavailableInput,
+            });
+        };
+
+        let Io::Kill(output) = input else {
+            return Err(Io::UnexpectedInput(Box::new(input)));
+        };
+
+        match output {
+            Ok(child) => {
+                debug!("successfully sent signal to child {}", child.id());
+                Ok(child)
+            }
+            Err(input) => {
+                debug!("need to send signal to child");
+                Err(Io::Kill(Err(input)))
+            }
+        }
+    }
+}
@@ -3,12 +3,61 @@
 //! Flows emit [`crate::Io`] requests that need to be processed by
 //! [`crate::handlers`] in order to continue their progression.
 
+#[cfg(unix)]
+mod expect;
+#[cfg(unix)]
+#[path = "job-pool.rs"]
+mod job_pool;
+#[cfg(unix)]
+mod kill;
+#[cfg(target_os = "linux")]
+#[path = "resize-pty.rs"]
+mod resize_pty;
+mod retry;
+mod spawn;
+#[cfg(unix)]
+#[path = "spawn-detached.rs"]
+mod spawn_detached;
+#[cfg(target_os = "linux")]
+#[path = "spawn-pty.rs"]
+mod spawn_pty;
+#[path = "spawn-then-capture.rs"]
+mod spawn_then_capture;
 #[path = "spawn-then-wait.rs"]
 mod spawn_then_wait;
 #[path = "spawn-then-wait-with-output.rs"]
 mod spawn_then_wait_with_output;
+mod supervise;
+#[cfg(unix)]
+mod terminate;
+mod wait;
+#[cfg(unix)]
+#[path = "wait-ready.rs"]
+mod wait_ready;
+#[cfg(unix)]
+mod xargs;
 
+#[cfg(unix)]
 #[doc(inline)]
 pub use self::{
-    spawn_then_wait::SpawnThenWait, spawn_then_wait_with_output::SpawnThenWaitWithOutput,
+    expect::{Expect, ExpectOutput, ExpectStep},
+    job_pool::JobPool,
+    kill::Kill,
+    spawn_detached::SpawnDetached,
+    terminate::{Terminate, TerminateOutput, Termination, TerminationPolicy},
+    wait_ready::{Probe, WaitReady, WaitReadyOutput},
+    xargs::{Xargs, XargsOutput, MAX_ARG_STRLEN},
+};
+#[cfg(target_os = "linux")]
+#[doc(inline)]
+pub use self::{resize_pty::ResizePty, spawn_pty::SpawnPty};
+#[doc(inline)]
+pub use self::{
+    retry::{Retry, RetryPolicy, Retryable},
+    spawn::Spawn,
+    spawn_then_capture::SpawnThenCapture,
+    spawn_then_wait::SpawnThenWait,
+    spawn_then_wait_with_output::SpawnThenWaitWithOutput,
+    supervise::{Restart, RestartPolicy, Supervise, SuperviseEvent},
+    wait::Wait,
 };
@@ -0,0 +1,50 @@
+//! Module dedicated to the I/O-free [`ResizePty`] coroutine.
+
+use log::debug;
+
+use crate::{Io, Pty, WindowSize};
+
+/// The I/O-free coroutine for resizing the window of a
+/// pseudo-terminal.
+///
+/// The child process receives a `SIGWINCH` signal, and the
+/// pseudo-terminal is given back once resized.
+#[derive(Debug)]
+pub struct ResizePty {
+    input: Option<(Pty, WindowSize)>,
+}
+
+impl ResizePty {
+    /// Creates a new coroutine from the given pseudo-terminal and its
+    /// new window size.
+    pub fn new(pty: Pty, size: WindowSize) -> Self {
+        debug!("prepare pty to be resized to {size:?}");
+        let input = Some((pty, size));
+        Self { input }
+    }
+
+    /// Makes the coroutine progress.
+    pub fn resume(&mut self, input: Option<Io>) -> Result<Pty, Io> {
+        let Some(input) = input else {
+            return Err(match self.input.take() {
+                Some(input) => Io::ResizePty(Err(input)),
+                None => Io::UnavailableInput,
+            });
+        };
+
+        let Io::ResizePty(output) = input else {
+            return Err(Io::UnexpectedInput(Box::new(input)));
+        };
+
+        match output {
+            Ok(pty) => {
+                debug!("successfully resized pty");
+                Ok(pty)
+            }
+            Err(input) => {
+                debug!("need to resize pty");
+                Err(Io::ResizePty(Err(input)))
+            }
+        }
+    }
+}
@@ -0,0 +1,349 @@
+//! Module dedicated to the I/O-free [`Retry`] coroutine.
+
+use std::{
+    collections::hash_map::RandomState,
+    fmt,
+    hash::{BuildHasher, Hasher},
+    process::ExitStatus,
+    time::Duration,
+};
+
+use log::debug;
+
+use crate::{Io, Output, SpawnOutput};
+
+use super::{SpawnThenWait, SpawnThenWaitWithOutput};
+
+/// The policy deciding whether and when a [`Retry`] coroutine
+/// re-runs its command.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RetryPolicy {
+    /// Maximum number of attempts, including the first one.
+    pub max_attempts: u32,
+
+    /// Delay before the first retry.
+    pub initial_delay: Duration,
+
+    /// Maximum delay between two attempts.
+    pub max_delay: Duration,
+
+    /// Factor applied to the delay after each retry.
+    pub multiplier: f64,
+
+    /// Whether the delay should be randomized, between half and the
+    /// full computed delay.
+    ///
+    /// Jitter prevents concurrent retries from running in lockstep.
+    pub jitter: bool,
+
+    /// Exit codes leading to a retry.
+    ///
+    /// When `None`, any unsuccessful exit status leads to a retry,
+    /// including termination by signal.
+    pub exit_codes: Option<Vec<i32>>,
+}
+
+impl RetryPolicy {
+    /// Sets the maximum number of attempts, including the first one.
+    pub fn max_attempts(mut self, max_attempts: u32) -> Self {
+        self.max_attempts = max_attempts;
+        self
+    }
+
+    /// Sets the exponential backoff parameters.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the multiplier is negative, infinite or NaN.
+    pub fn backoff(
+        mut self,
+        initial_delay: Duration,
+        max_delay: Duration,
+        multiplier: f64,
+    ) -> Self {
+        assert_multiplier(multiplier);
+        self.initial_delay = initial_delay;
+        self.max_delay = max_delay;
+        self.multiplier = multiplier;
+        self
+    }
+
+    /// Sets whether the delay should be randomized.
+    pub fn jitter(mut self, jitter: bool) -> Self {
+        self.jitter = jitter;
+        self
+    }
+
+    /// Restricts retries to the given exit codes.
+    pub fn exit_codes(mut self, codes: impl IntoIterator<Item = i32>) -> Self {
+        self.exit_codes = Some(codes.into_iter().collect());
+        self
+    }
+
+    /// Returns `true` if the given exit status of the given attempt
+    /// (starting at 1) should lead to a retry.
+    pub fn should_retry(&self, attempt: u32, status: &ExitStatus) -> bool {
+        if status.success() || attempt >= self.max_attempts {
+            return false;
+        }
+
+        match (&self.exit_codes, status.code()) {
+            (None, _) => true,
+            (Some(codes), Some(code)) => codes.contains(&code),
+            (Some(_), None) => false,
+        }
+    }
+
+    /// Returns the delay to wait after the given failed attempt
+    /// (starting at 1).
+    pub fn delay(&self, attempt: u32) -> Duration {
+        let delay = backoff(self.initial_delay, self.max_delay, self.multiplier, attempt);
+
+        if !self.jitter {
+            return delay;
+        }
+
+        let mut hasher = RandomState::new().build_hasher();
+        hasher.write_u32(attempt);
+        let ratio = hasher.finish() as f64 / u64::MAX as f64;
+
+        delay.mul_f64(0.5 + ratio / 2.0)
+    }
+}
+
+/// Ensures that the given backoff multiplier is finite and positive.
+pub(super) fn assert_multiplier(multiplier: f64) {
+    assert!(
+        multiplier.is_finite() && multiplier >= 0.0,
+        "backoff multiplier must be finite and positive, got {multiplier}",
+    );
+}
+
+/// Returns the exponential backoff delay before the given retry
+/// (starting at 1), saturating to the given maximum delay.
+///
+/// Invalid multipliers, which can only be set through public fields,
+/// are ignored: the initial delay is then always used.
+pub(super) fn backoff(initial: Duration, max: Duration, multiplier: f64, retry: u32) -> Duration {
+    if initial.is_zero() {
+        return Duration::ZERO;
+    }
+
+    let multiplier = match multiplier {
+        m if m.is_finite() && m >= 0.0 => m,
+        _ => 1.0,
+    };
+
+    let exponent = retry.saturating_sub(1).min(i32::MAX as u32) as i32;
+    let secs = initial.as_secs_f64() * multiplier.powi(exponent);
+
+    Duration::try_from_secs_f64(secs).unwrap_or(max).min(max)
+}
+
+/// The default policy makes 3 attempts, waiting 1 second then 2
+/// seconds (before jitter) between them.
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            initial_delay: Duration::from_secs(1),
+            max_delay: Duration::from_secs(60),
+            multiplier: 2.0,
+            jitter: true,
+            exit_codes: None,
+        }
+    }
+}
+
+/// The spawn coroutine that can be wrapped by [`Retry`].
+///
+/// This trait is implemented for [`SpawnThenWait`] and
+/// [`SpawnThenWaitWithOutput`].
+pub trait Retryable {
+    /// The output of the coroutine.
+    type Output;
+
+    /// Makes the coroutine progress.
+    fn resume(&mut self, input: Option<Io>) -> Result<Self::Output, Io>;
+
+    /// Returns the exit status of the given output.
+    fn status(output: &Self::Output) -> &ExitStatus;
+}
+
+impl Retryable for SpawnThenWait {
+    type Output = SpawnOutput;
+
+    fn resume(&mut self, input: Option<Io>) -> Result<Self::Output, Io> {
+        SpawnThenWait::resume(self, input)
+    }
+
+    fn status(output: &Self::Output) -> &ExitStatus {
+        &output.status
+    }
+}
+
+impl Retryable for SpawnThenWaitWithOutput {
+    type Output = Output;
+
+    fn resume(&mut self, input: Option<Io>) -> Result<Self::Output, Io> {
+        SpawnThenWaitWithOutput::resume(self, input)
+    }
+
+    fn status(output: &Self::Output) -> &ExitStatus {
+        &output.status
+    }
+}
+
+/// The I/O-free coroutine for re-running a spawn coroutine until it
+/// succeeds, according to a [`RetryPolicy`].
+///
+/// This coroutine should be used for commands failing transiently,
+/// like network-dependent ones. Delays between attempts are emitted
+/// as [`Io::Sleep`] requests.
+///
+/// The output of the last attempt is returned, whether it succeeded
+/// or not. Each attempt runs a new spawn coroutine, built by the
+/// given function (see [`crate::Command`]'s [`Clone`] implementation
+/// for why).
+///
+/// ```rust,ignore
+/// let spawn = || SpawnThenWaitWithOutput::new(command()).limit(limit);
+/// let mut retry = Retry::new(spawn, RetryPolicy::default());
+/// ```
+pub struct Retry<C: Retryable> {
+    spawn: Box<dyn Fn() -> C + Send>,
+    policy: RetryPolicy,
+    coroutine: C,
+    attempt: u32,
+    sleeping: bool,
+}
+
+impl<C: Retryable> Retry<C> {
+    /// Creates a new coroutine from the given function building the
+    /// spawn coroutine of each attempt, and the given retry policy.
+    pub fn new(spawn: impl Fn() -> C + Send + 'static, policy: RetryPolicy) -> Self {
+        Self {
+            coroutine: spawn(),
+            spawn: Box::new(spawn),
+            policy,
+            attempt: 1,
+            sleeping: false,
+        }
+    }
+
+    /// Makes the coroutine progress.
+    pub fn resume(&mut self, mut input: Option<Io>) -> Result<C::Output, Io> {
+        if self.sleeping {
+            match input.take() {
+                Some(Io::Sleep(Ok(()))) => {
+                    self.sleeping = false;
+                    self.attempt += 1;
+                    self.coroutine = (self.spawn)();
+                    debug!("retry command, attempt {}", self.attempt);
+                }
+                Some(Io::Sleep(Err(delay))) => return Err(Io::Sleep(Err(delay))),
+                Some(input) => return Err(Io::UnexpectedInput(Box::new(input))),
+                None => return Err(Io::UnavailableInput),
+            }
+        }
+
+        let output = self.coroutine.resume(input)?;
+        let status = C::status(&output);
+
+        if !self.policy.should_retry(self.attempt, status) {
+            return Ok(output);
+        }
+
+        let delay = self.policy.delay(self.attempt);
+        debug!(
+            "attempt {} failed with {status}, retry in {delay:?}",
+            self.attempt
+        );
+
+        self.sleeping = true;
+        Err(Io::Sleep(Err(delay)))
+    }
+}
+
+impl<C: Retryable + fmt::Debug> fmt::Debug for Retry<C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Retry")
+            .field("policy", &self.policy)
+            .field("coroutine", &self.coroutine)
+            .field("attempt", &self.attempt)
+            .field("sleeping", &self.sleeping)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use std::{os::unix::process::ExitStatusExt, process::ExitStatus, time::Duration};
+
+    use super::RetryPolicy;
+
+    #[test]
+    fn should_retry() {
+        let policy = RetryPolicy::default().exit_codes([75]);
+        let success = ExitStatus::from_raw(0);
+        let tempfail = ExitStatus::from_raw(75 << 8);
+        let failure = ExitStatus::from_raw(1 << 8);
+        let killed = ExitStatus::from_raw(libc::SIGKILL);
+
+        assert!(!policy.should_retry(1, &success));
+        assert!(policy.should_retry(1, &tempfail));
+        assert!(policy.should_retry(2, &tempfail));
+        assert!(!policy.should_retry(3, &tempfail));
+        assert!(!policy.should_retry(1, &failure));
+        assert!(!policy.should_retry(1, &killed));
+
+        let policy = RetryPolicy::default();
+        assert!(policy.should_retry(1, &failure));
+        assert!(policy.should_retry(1, &killed));
+    }
+
+    #[test]
+    fn delay() {
+        let secs = Duration::from_secs;
+        let policy = RetryPolicy::default()
+            .backoff(secs(1), secs(5), 2.0)
+            .jitter(false);
+
+        assert_eq!(secs(1), policy.delay(1));
+        assert_eq!(secs(2), policy.delay(2));
+        assert_eq!(secs(4), policy.delay(3));
+        assert_eq!(secs(5), policy.delay(4));
+
+        let policy = policy.jitter(true);
+
+        for attempt in 1..5 {
+            let delay = policy.delay(attempt);
+            let max = policy.clone().jitter(false).delay(attempt);
+            assert!(delay >= max / 2 && delay <= max);
+        }
+    }
+
+    #[test]
+    fn delay_saturates() {
+        let secs = Duration::from_secs;
+        let mut policy = RetryPolicy::default()
+            .backoff(secs(1), secs(5), 2.0)
+            .jitter(false);
+
+        assert_eq!(secs(5), policy.delay(1000));
+        assert_eq!(secs(5), policy.delay(u32::MAX));
+
+        policy.multiplier = f64::NAN;
+        assert_eq!(secs(1), policy.delay(u32::MAX));
+
+        let policy = policy.backoff(Duration::ZERO, secs(5), 2.0);
+        assert_eq!(Duration::ZERO, policy.delay(u32::MAX));
+    }
+
+    #[test]
+    #[should_panic = "backoff multiplier must be finite and positive"]
+    fn negative_multiplier() {
+        let secs = Duration::from_secs;
+        let _ = RetryPolicy::default().backoff(secs(1), secs(5), -2.0);
+    }
+}
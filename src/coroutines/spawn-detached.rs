@@ -0,0 +1,57 @@
+//! Module dedicated to the I/O-free [`SpawnDetached`] coroutine.
+
+use log::debug;
+
+use crate::{Command, Io};
+
+/// The I/O-free coroutine for spawning a detached process, which
+/// outlives its parent.
+///
+/// This coroutine should be used for background programs, like
+/// notification daemons. The process runs in a new session, is
+/// reparented to init (or to the closest subreaper) so that it never
+/// becomes a zombie of the parent, and cannot be waited.
+///
+/// The std{in,out,err} not explicitly configured are redirected to
+/// `/dev/null`. Output can be kept in a log file using
+/// [`Command::stdout_file`] and [`Command::stderr_file`].
+///
+/// The ID of the detached process is returned.
+#[derive(Debug)]
+pub struct SpawnDetached {
+    command: Option<Command>,
+}
+
+impl SpawnDetached {
+    /// Creates a new coroutine from the given command builder.
+    pub fn new(command: Command) -> Self {
+        debug!("prepare command to be spawned detached: {command:?}");
+        let command = Some(command);
+        Self { command }
+    }
+
+    /// Makes the coroutine progress.
+    pub fn resume(&mut self, input: Option<Io>) -> Result<u32, Io> {
+        let Some(input) = input else {
+            return Err(match self.command.take() {
+                Some(cmd) => Io::SpawnDetached(Err(cmd)),
+                None => Io::UnavailableInput,
+            });
+        };
+
+        let Io::SpawnDetached(output) = input else {
+            return Err(Io::UnexpectedInput(Box::new(input)));
+        };
+
+        match output {
+            Ok(pid) => {
+                debug!("successfully spawned detached process {pid}");
+                Ok(pid)
+            }
+            Err(io) => {
+                debug!("need to spawn detached command");
+                Err(Io::SpawnDetached(Err(io)))
+            }
+        }
+    }
+}
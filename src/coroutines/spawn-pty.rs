@@ -0,0 +1,55 @@
+//! Module dedicated to the I/O-free [`SpawnPty`] coroutine.
+
+use log::debug;
+
+use crate::{Command, Io, Pty, WindowSize};
+
+/// The I/O-free coroutine for spawning a process in a new
+/// pseudo-terminal.
+///
+/// This coroutine should be used for interactive programs that
+/// require a terminal, like editors, `pinentry-curses` or `ssh`. Any
+/// std{in,out,err} configuration of the command is replaced by the
+/// pseudo-terminal, and the child process leads a new session.
+///
+/// The resulting [`Pty`] child process should eventually be waited
+/// using [`super::Wait`].
+#[derive(Debug)]
+pub struct SpawnPty {
+    input: Option<(Command, WindowSize)>,
+}
+
+impl SpawnPty {
+    /// Creates a new coroutine from the given command builder and
+    /// the initial size of the pseudo-terminal window.
+    pub fn new(command: Command, size: WindowSize) -> Self {
+        debug!("prepare command to be spawned in a pty: {command:?}");
+        let input = Some((command, size));
+        Self { input }
+    }
+
+    /// Makes the coroutine progress.
+    pub fn resume(&mut self, input: Option<Io>) -> Result<Pty, Io> {
+        let Some(input) = input else {
+            return Err(match self.input.take() {
+                Some(input) => Io::SpawnPty(Err(input)),
+                None => Io::UnavailableInput,
+            });
+        };
+
+        let Io::SpawnPty(output) = input else {
+            return Err(Io::UnexpectedInput(Box::new(input)));
+        };
+
+        match output {
+            Ok(pty) => {
+                debug!("successfully spawned command in a pty: {pty:?}");
+                Ok(pty)
+            }
+            Err(input) => {
+                debug!("need to spawn command in a pty");
+                Err(Io::SpawnPty(Err(input)))
+            }
+        }
+    }
+}
@@ -0,0 +1,53 @@
+//! Module dedicated to the I/O-free [`SpawnThenCapture`] coroutine.
+
+use log::debug;
+
+use crate::{Capture, CaptureOutput, Command, Io};
+
+/// The I/O-free coroutine for spawning a process then capturing its
+/// output, according to a [`Capture`] configuration.
+///
+/// Like [`super::SpawnThenWaitWithOutput`], this coroutine can bound
+/// the memory used to capture each stream, so that a runaway child
+/// process cannot exhaust it. It can additionally merge, tag or
+/// forward the output while capturing it.
+#[derive(Debug)]
+pub struct SpawnThenCapture {
+    input: Option<(Command, Capture)>,
+}
+
+impl SpawnThenCapture {
+    /// Creates a new coroutine from the given command builder and
+    /// capture configuration.
+    pub fn new(command: Command, capture: Capture) -> Self {
+        debug!("prepare command to be spawned: {command:?}");
+        debug!("prepare output to be captured: {capture:?}");
+        let input = Some((command, capture));
+        Self { input }
+    }
+
+    /// Makes the coroutine progress.
+    pub fn resume(&mut self, input: Option<Io>) -> Result<CaptureOutput, Io> {
+        let Some(input) = input else {
+            return Err(match self.input.take() {
+                Some(input) => Io::SpawnThenCapture(Err(input)),
+                None => Io::UnavailableInput,
+            });
+        };
+
+        let Io::SpawnThenCapture(output) = input else {
+            return Err(Io::UnexpectedInput(Box::new(input)));
+        };
+
+        match output {
+            Ok(output) => {
+                debug!("successfully captured command output: {output:?}");
+                Ok(output)
+            }
+            Err(io) => {
+                debug!("need to spawn command then capture its output");
+                Err(Io::SpawnThenCapture(Err(io)))
+            }
+        }
+    }
+}
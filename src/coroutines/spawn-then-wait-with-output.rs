@@ -1,11 +1,9 @@
 //! Module dedicated to the I/O-free [`SpawnThenWaitWithOutput`]
 //! coroutine.
 
-use std::process::Output;
-
 use log::debug;
 
-use crate::{Command, Io};
+use crate::{Command, Io, Output, OutputLimit};
 
 /// The I/O-free coroutine for spawning a process then waiting for its
 /// child's output.
@@ -15,9 +13,17 @@ use crate::{Command, Io};
 ///
 /// If you do not need to collect the output, or if you need to pipe
 /// the output to another process, see [`super::SpawnThenWait`].
+///
+/// The output is unbounded by default. A limit can be set for each
+/// stream using [`SpawnThenWaitWithOutput::stdout_limit`] and
+/// [`SpawnThenWaitWithOutput::stderr_limit`], the number of bytes
+/// discarded being reported in the [`Output`]. If you need to tag or
+/// to forward the output, see [`super::SpawnThenCapture`].
 #[derive(Debug)]
 pub struct SpawnThenWaitWithOutput {
     command: Option<Command>,
+    stdout_limit: Option<OutputLimit>,
+    stderr_limit: Option<OutputLimit>,
 }
 
 impl SpawnThenWaitWithOutput {
@@ -25,14 +31,39 @@ impl SpawnThenWaitWithOutput {
     pub fn new(command: Command) -> Self {
         debug!("prepare command to be spawned: {command:?}");
         let command = Some(command);
-        Self { command }
+        Self {
+            command,
+            stdout_limit: None,
+            stderr_limit: None,
+        }
+    }
+
+    /// Limits the number of bytes collected from each of stdout and
+    /// stderr.
+    pub fn limit(self, limit: OutputLimit) -> Self {
+        self.stdout_limit(limit).stderr_limit(limit)
+    }
+
+    /// Limits the number of bytes collected from stdout.
+    pub fn stdout_limit(mut self, limit: OutputLimit) -> Self {
+        self.stdout_limit = Some(limit);
+        self
+    }
+
+    /// Limits the number of bytes collected from stderr.
+    pub fn stderr_limit(mut self, limit: OutputLimit) -> Self {
+        self.stderr_limit = Some(limit);
+        self
     }
 
     /// Makes the coroutine progress.
     pub fn resume(&mut self, input: Option<Io>) -> Result<Output, Io> {
         let Some(input) = input else {
             return Err(match self.command.take() {
-                Some(cmd) => Io::SpawnThenWaitWithOutput(Err(cmd)),
+                Some(cmd) => {
+                    let limits = (cmd, self.stdout_limit, self.stderr_limit);
+                    Io::SpawnThenWaitWithOutput(Err(limits))
+                }
                 None => Io::UnavailableInput,
             });
         };
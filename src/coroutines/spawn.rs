@@ -0,0 +1,51 @@
+//! Module dedicated to the I/O-free [`Spawn`] coroutine.
+
+use log::debug;
+
+use crate::{Child, Command, Io};
+
+/// The I/O-free coroutine for spawning a process without waiting for
+/// it.
+///
+/// This coroutine should be used when the child process needs to be
+/// controlled while running, for example to send it signals using
+/// [`super::Kill`]. The resulting [`Child`] should eventually be
+/// waited using [`super::Wait`].
+#[derive(Debug)]
+pub struct Spawn {
+    command: Option<Command>,
+}
+
+impl Spawn {
+    /// Creates a new coroutine from the given command builder.
+    pub fn new(command: Command) -> Self {
+        debug!("prepare command to be spawned: {command:?}");
+        let command = Some(command);
+        Self { command }
+    }
+
+    /// Makes the coroutine progress.
+    pub fn resume(&mut self, input: Option<Io>) -> Result<Child, Io> {
+        let Some(input) = input else {
+            return Err(match self.command.take() {
+                Some(cmd) => Io::Spawn(Err(cmd)),
+                None => Io::UnavailableInput,
+            });
+        };
+
+        let Io::Spawn(output) = input else {
+            return Err(Io::UnexpectedInput(Box::new(input)));
+        };
+
+        match output {
+            Ok(child) => {
+                debug!("successfully spawned command: {child:?}");
+                Ok(child)
+            }
+            Err(io) => {
+                debug!("need to spawn command");
+                Err(Io::Spawn(Err(io)))
+            }
+        }
+    }
+}
@@ -0,0 +1,369 @@
+//! Module dedicated to the I/O-free [`Supervise`] coroutine.
+
+use std::{
+    collections::VecDeque,
+    fmt, mem,
+    process::ExitStatus,
+    time::{Duration, Instant},
+};
+
+use log::debug;
+
+use crate::{Child, Command, Io, SpawnOutput};
+
+use super::retry;
+
+/// The exits leading a [`Supervise`] coroutine to restart its
+/// command.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum Restart {
+    /// Restarts the command whatever its exit status.
+    Always,
+
+    /// Restarts the command when it exits unsuccessfully, including
+    /// termination by signal.
+    #[default]
+    OnFailure,
+}
+
+/// The policy deciding whether and when a [`Supervise`] coroutine
+/// restarts its command.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RestartPolicy {
+    /// Exits leading to a restart.
+    pub restart: Restart,
+
+    /// Maximum number of restarts within [`RestartPolicy::window`].
+    ///
+    /// When `None`, the command is restarted indefinitely.
+    pub max_restarts: Option<u32>,
+
+    /// Sliding window in which restarts are counted.
+    pub window: Duration,
+
+    /// Delay before the first restart within the window.
+    pub initial_delay: Duration,
+
+    /// Maximum delay before a restart.
+    pub max_delay: Duration,
+
+    /// Factor applied to the delay after each restart within the
+    /// window.
+    pub multiplier: f64,
+}
+
+impl RestartPolicy {
+    /// Sets the exits leading to a restart.
+    pub fn restart(mut self, restart: Restart) -> Self {
+        self.restart = restart;
+        self
+    }
+
+    /// Limits the number of restarts within the given sliding
+    /// window.
+    pub fn max_restarts(mut self, max_restarts: u32, window: Duration) -> Self {
+        self.max_restarts = Some(max_restarts);
+        self.window = window;
+        self
+    }
+
+    /// Restarts the command indefinitely.
+    pub fn unlimited(mut self) -> Self {
+        self.max_restarts = None;
+        self
+    }
+
+    /// Sets the exponential backoff parameters.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the multiplier is negative, infinite or NaN.
+    pub fn backoff(
+        mut self,
+        initial_delay: Duration,
+        max_delay: Duration,
+        multiplier: f64,
+    ) -> Self {
+        retry::assert_multiplier(multiplier);
+        self.initial_delay = initial_delay;
+        self.max_delay = max_delay;
+        self.multiplier = multiplier;
+        self
+    }
+
+    /// Returns `true` if the given exit status should lead to a
+    /// restart, regardless of the restart limit.
+    pub fn should_restart(&self, status: &ExitStatus) -> bool {
+        match self.restart {
+            Restart::Always => true,
+            Restart::OnFailure => !status.success(),
+        }
+    }
+
+    /// Returns the delay to wait before the given restart within the
+    /// window (starting at 1), saturating to the maximum delay.
+    pub fn delay(&self, restart: u32) -> Duration {
+        let (initial, max) = (self.initial_delay, self.max_delay);
+        retry::backoff(initial, max, self.multiplier, restart)
+    }
+}
+
+/// The default policy restarts failing commands at most 5 times per
+/// minute, waiting 1 second then 2 seconds, 4 seconds and so on
+/// between them.
+impl Default for RestartPolicy {
+    fn default() -> Self {
+        Self {
+            restart: Restart::OnFailure,
+            max_restarts: Some(5),
+            window: Duration::from_secs(60),
+            initial_delay: Duration::from_secs(1),
+            max_delay: Duration::from_secs(60),
+            multiplier: 2.0,
+        }
+    }
+}
+
+/// The lifecycle event output by the [`Supervise`] coroutine.
+#[derive(Debug)]
+pub enum SuperviseEvent {
+    /// The command was spawned, with the given process ID.
+    Spawned(u32),
+
+    /// The child process exited.
+    Exited(SpawnOutput),
+
+    /// The command is about to be restarted, after the given delay.
+    Restarting(Duration),
+
+    /// The command reached the maximum number of restarts within the
+    /// window, and is not restarted anymore.
+    GaveUp,
+}
+
+#[derive(Debug)]
+enum State {
+    Spawn,
+    Wait(Child),
+    Restart(Duration),
+    Sleep(Duration),
+    GiveUp,
+    Done,
+    Pending,
+}
+
+/// The I/O-free coroutine for keeping a long-lived command running,
+/// according to a [`RestartPolicy`].
+///
+/// This coroutine should be used for helper processes like watchers
+/// or synchronization daemons. Like [`super::JobPool`], it outputs
+/// each [`SuperviseEvent`] as soon as it happens, then `None` once
+/// the command is not restarted anymore. Delays between restarts are
+/// emitted as [`Io::Sleep`] requests.
+///
+/// Each run spawns a new command, built by the given function (see
+/// [`Command`]'s [`Clone`] implementation for why).
+///
+/// ```rust,ignore
+/// let mut arg = None;
+/// let mut supervise = Supervise::new(build_command, RestartPolicy::default());
+///
+/// while let Some(event) = loop {
+///     match supervise.resume(arg.take()) {
+///         Ok(event) => break event,
+///         Err(io) => arg = Some(handle(io).unwrap()),
+///     }
+/// } {
+///     println!("{event:?}");
+/// }
+/// ```
+pub struct Supervise {
+    command: Box<dyn Fn() -> Command + Send>,
+    policy: RestartPolicy,
+    restarts: VecDeque<Instant>,
+    stopped: bool,
+    state: State,
+}
+
+impl Supervise {
+    /// Creates a new coroutine from the given function building the
+    /// command, and the given restart policy.
+    pub fn new(command: impl Fn() -> Command + Send + 'static, policy: RestartPolicy) -> Self {
+        debug!("prepare command to be supervised");
+
+        Self {
+            command: Box::new(command),
+            policy,
+            restarts: VecDeque::new(),
+            stopped: false,
+            state: State::Spawn,
+        }
+    }
+
+    /// Stops the supervision: the command is not restarted anymore.
+    ///
+    /// If the child process is running and held by the coroutine
+    /// (right after a [`SuperviseEvent::Spawned`] event), it is given
+    /// back so that it can be terminated, see [`super::Terminate`].
+    /// Otherwise, the supervision ends at its next exit.
+    pub fn stop(&mut self) -> Option<Child> {
+        debug!("stop supervision");
+        self.stopped = true;
+
+        match mem::replace(&mut self.state, State::Done) {
+            State::Wait(child) => Some(child),
+            State::Pending => {
+                self.state = State::Pending;
+                None
+            }
+            _ => None,
+        }
+    }
+
+    /// Makes the coroutine progress.
+    pub fn resume(&mut self, input: Option<Io>) -> Result<Option<SuperviseEvent>, Io> {
+        match input {
+            None => (),
+            Some(Io::Spawn(Ok(child))) => {
+                let pid = child.id();
+                debug!("spawned supervised child {pid}");
+                self.state = State::Wait(child);
+                return Ok(Some(SuperviseEvent::Spawned(pid)));
+            }
+            Some(Io::Wait(Ok(output))) => {
+                debug!("supervised child exited with {}", output.status);
+                self.state = self.next_state(&output.status);
+                return Ok(Some(SuperviseEvent::Exited(output)));
+            }
+            Some(Io::Sleep(Ok(()))) if self.stopped => {
+                debug!("supervision stopped during backoff, do not restart");
+                self.state = State::Done;
+            }
+            Some(Io::Sleep(Ok(()))) => {
+                self.restarts.push_back(Instant::now());
+                self.state = State::Spawn;
+            }
+            Some(Io::Spawn(Err(command))) => return Err(Io::Spawn(Err(command))),
+            Some(Io::Wait(Err(child))) => return Err(Io::Wait(Err(child))),
+            Some(Io::Sleep(Err(delay))) => return Err(Io::Sleep(Err(delay))),
+            Some(input) => return Err(Io::UnexpectedInput(Box::new(input))),
+        }
+
+        match mem::replace(&mut self.state, State::Pending) {
+            State::Spawn => {
+                debug!("need to spawn supervised command");
+                Err(Io::Spawn(Err((self.command)())))
+            }
+            State::Wait(child) => {
+                debug!("need to wait supervised child {}", child.id());
+                Err(Io::Wait(Err(child)))
+            }
+            State::Restart(delay) => {
+                self.state = State::Sleep(delay);
+                Ok(Some(SuperviseEvent::Restarting(delay)))
+            }
+            State::Sleep(delay) => Err(Io::Sleep(Err(delay))),
+            State::GiveUp => {
+                debug!("reached maximum number of restarts, give up");
+                self.state = State::Done;
+                Ok(Some(SuperviseEvent::GaveUp))
+            }
+            State::Done => {
+                self.state = State::Done;
+                Ok(None)
+            }
+            State::Pending => Err(Io::UnavailableInput),
+        }
+    }
+
+    /// Returns the state following the given exit status, according
+    /// to the restart policy.
+    fn next_state(&mut self, status: &ExitStatus) -> State {
+        if self.stopped || !self.policy.should_restart(status) {
+            return State::Done;
+        }
+
+        let now = Instant::now();
+
+        while let Some(restart) = self.restarts.front() {
+            if now.duration_since(*restart) <= self.policy.window {
+                break;
+            }
+
+            self.restarts.pop_front();
+        }
+
+        let restarts = self.restarts.len() as u32;
+
+        match self.policy.max_restarts {
+            Some(max) if restarts >= max => State::GiveUp,
+            _ => State::Restart(self.policy.delay(restarts + 1)),
+        }
+    }
+}
+
+impl fmt::Debug for Supervise {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Supervise")
+            .field("policy", &self.policy)
+            .field("restarts", &self.restarts)
+            .field("stopped", &self.stopped)
+            .field("state", &self.state)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use std::{os::unix::process::ExitStatusExt, process::ExitStatus, time::Duration};
+
+    use super::{Restart, RestartPolicy};
+
+    #[test]
+    fn should_restart() {
+        let success = ExitStatus::from_raw(0);
+        let failure = ExitStatus::from_raw(1 << 8);
+        let killed = ExitStatus::from_raw(libc::SIGKILL);
+
+        let policy = RestartPolicy::default();
+        assert!(!policy.should_restart(&success));
+        assert!(policy.should_restart(&failure));
+        assert!(policy.should_restart(&killed));
+
+        let policy = policy.restart(Restart::Always);
+        assert!(policy.should_restart(&success));
+    }
+
+    #[test]
+    fn delay() {
+        let secs = Duration::from_secs;
+        let policy = RestartPolicy::default().backoff(secs(1), secs(5), 2.0);
+
+        assert_eq!(secs(1), policy.delay(1));
+        assert_eq!(secs(2), policy.delay(2));
+        assert_eq!(secs(4), policy.delay(3));
+        assert_eq!(secs(5), policy.delay(4));
+    }
+
+    #[test]
+    fn delay_saturates() {
+        let secs = Duration::from_secs;
+        let mut policy = RestartPolicy::default().backoff(secs(1), secs(5), 2.0);
+
+        assert_eq!(secs(5), policy.delay(1000));
+        assert_eq!(secs(5), policy.delay(u32::MAX));
+
+        policy.multiplier = f64::INFINITY;
+        assert_eq!(secs(1), policy.delay(u32::MAX));
+
+        let policy = policy.backoff(Duration::ZERO, secs(5), 2.0);
+        assert_eq!(Duration::ZERO, policy.delay(u32::MAX));
+    }
+
+    #[test]
+    #[should_panic = "backoff multiplier must be finite and positive"]
+    fn negative_multiplier() {
+        let secs = Duration::from_secs;
+        let _ = RestartPolicy::default().backoff(secs(1), secs(5), f64::NAN);
+    }
+}
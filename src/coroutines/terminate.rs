@@ -0,0 +1,214 @@
+//! Module dedicated to the I/O-free [`Terminate`] coroutine.
+
+use std::time::Duration;
+
+use log::debug;
+
+use crate::{Child, Io, Signal, SpawnOutput};
+
+/// The policy deciding how a [`Terminate`] coroutine stops a child
+/// process.
+///
+/// The child process is first sent [`TerminationPolicy::signal`] so
+/// that it can clean up, then [`TerminationPolicy::escalation`] if
+/// it is still running after [`TerminationPolicy::grace`].
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct TerminationPolicy {
+    /// Signal sent first to the child process.
+    pub signal: Signal,
+
+    /// Period given to the child process to exit after the first
+    /// signal.
+    pub grace: Duration,
+
+    /// Signal sent to the child process still running after the
+    /// grace period.
+    pub escalation: Signal,
+}
+
+impl TerminationPolicy {
+    /// Sets the signal sent first to the child process.
+    pub fn signal(mut self, signal: Signal) -> Self {
+        self.signal = signal;
+        self
+    }
+
+    /// Sets the period given to the child process to exit after the
+    /// first signal.
+    pub fn grace(mut self, grace: Duration) -> Self {
+        self.grace = grace;
+        self
+    }
+
+    /// Sets the signal sent to the child process still running after
+    /// the grace period.
+    pub fn escalation(mut self, signal: Signal) -> Self {
+        self.escalation = signal;
+        self
+    }
+}
+
+/// The default policy sends [`Signal::TERM`], then [`Signal::KILL`]
+/// after 5 seconds.
+impl Default for TerminationPolicy {
+    fn default() -> Self {
+        Self {
+            signal: Signal::TERM,
+            grace: Duration::from_secs(5),
+            escalation: Signal::KILL,
+        }
+    }
+}
+
+/// The way a child process ended, see [`TerminateOutput`].
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Termination {
+    /// The child process exited on its own, before the timeout.
+    Exited,
+
+    /// The child process exited within the grace period following
+    /// the first signal.
+    Graceful,
+
+    /// The child process was still running after the grace period,
+    /// and was sent the escalation signal.
+    Escalated,
+}
+
+/// The output of the [`Terminate`] coroutine.
+#[derive(Debug)]
+pub struct TerminateOutput {
+    /// The output of the terminated child process.
+    pub output: SpawnOutput,
+
+    /// The way the child process ended.
+    pub termination: Termination,
+}
+
+impl TerminateOutput {
+    /// Returns `true` if the child process did not need the
+    /// escalation signal to exit.
+    pub fn is_graceful(&self) -> bool {
+        self.termination != Termination::Escalated
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+enum State {
+    Timeout(Duration),
+    Signal,
+    Grace,
+    Escalation,
+    Reap,
+}
+
+/// The I/O-free coroutine for terminating a spawned child process,
+/// according to a [`TerminationPolicy`].
+///
+/// Like [`super::Kill`], signals are sent to the whole process group
+/// if the child process leads one. Grace periods are emitted as
+/// [`Io::WaitTimeout`] requests, so that the child process is reaped
+/// as soon as it exits.
+///
+/// An optional timeout lets the child process run until it expires
+/// before being terminated.
+///
+/// ```rust,ignore
+/// let mut terminate = Terminate::new(child, TerminationPolicy::default());
+/// ```
+#[derive(Debug)]
+pub struct Terminate {
+    child: Option<Child>,
+    policy: TerminationPolicy,
+    state: State,
+}
+
+impl Terminate {
+    /// Creates a new coroutine from the given child process and
+    /// termination policy.
+    pub fn new(child: Child, policy: TerminationPolicy) -> Self {
+        debug!("prepare child {} to be terminated", child.id());
+
+        Self {
+            child: Some(child),
+            policy,
+            state: State::Signal,
+        }
+    }
+
+    /// Lets the child process run for at most the given duration
+    /// before terminating it.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.state = State::Timeout(timeout);
+        self
+    }
+
+    /// Makes the coroutine progress.
+    pub fn resume(&mut self, input: Option<Io>) -> Result<TerminateOutput, Io> {
+        let Some(input) = input else {
+            let Some(child) = self.child.take() else {
+                return Err(Io::UnavailableInput);
+            };
+
+            return Err(match self.state {
+                State::Timeout(timeout) => Io::WaitTimeout(Err((child, timeout))),
+                _ => Io::Kill(Err((child, self.policy.signal))),
+            });
+        };
+
+        let output = |output, termination| {
+            debug!("successfully terminated child: {termination:?}");
+            Ok(TerminateOutput {
+                output,
+                termination,
+            })
+        };
+
+        match (self.state, input) {
+            (State::Timeout(_), Io::WaitTimeout(Ok(Ok(out)))) => output(out, Termination::Exited),
+            (State::Timeout(_), Io::WaitTimeout(Ok(Err(child)))) => {
+                debug!(
+                    "child {} timed out, send {}",
+                    child.id(),
+                    self.policy.signal
+                );
+                self.state = State::Signal;
+                Err(Io::Kill(Err((child, self.policy.signal))))
+            }
+            (State::Signal, Io::Kill(Ok(child))) => {
+                debug!(
+                    "wait {:?} for child {} to exit",
+                    self.policy.grace,
+                    child.id()
+                );
+                self.state = State::Grace;
+                Err(Io::WaitTimeout(Err((child, self.policy.grace))))
+            }
+            (State::Grace, Io::WaitTimeout(Ok(Ok(out)))) => output(out, Termination::Graceful),
+            (State::Grace, Io::WaitTimeout(Ok(Err(child)))) => {
+                let signal = self.policy.escalation;
+                debug!("child {} still running, send {signal}", child.id());
+                self.state = State::Escalation;
+                Err(Io::Kill(Err((child, signal))))
+            }
+            (State::Escalation, Io::Kill(Ok(child))) => {
+                self.state = State::Reap;
+                Err(Io::Wait(Err(child)))
+            }
+            (State::Reap, Io::Wait(Ok(out))) => output(out, Termination::Escalated),
+            (_, Io::WaitTimeout(Err(input))) => {
+                debug!("need to wait child with timeout");
+                Err(Io::WaitTimeout(Err(input)))
+            }
+            (_, Io::Kill(Err(input))) => {
+                debug!("need to send signal to child");
+                Err(Io::Kill(Err(input)))
+            }
+            (_, Io::Wait(Err(child))) => {
+                debug!("need to wait child");
+                Err(Io::Wait(Err(child)))
+            }
+            (_, input) => Err(Io::UnexpectedInput(Box::new(input))),
+        }
+    }
+}
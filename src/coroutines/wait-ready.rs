@@ -0,0 +1,294 @@
+//! Module dedicated to the I/O-free [`WaitReady`] coroutine.
+
+use std::{
+    fs::FileType,
+    mem,
+    os::unix::fs::FileTypeExt,
+    path::PathBuf,
+    time::{Duration, Instant},
+};
+
+use log::debug;
+
+use crate::{Child, Io, ReadOutput, SpawnOutput, Stream};
+
+use super::{Terminate, TerminateOutput, TerminationPolicy};
+
+/// The condition a [`WaitReady`] coroutine waits for.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Probe {
+    /// Ready once a line of the child process' stdout contains the
+    /// given bytes, anywhere in the line: this is a substring match,
+    /// not a whole line match. An empty pattern matches any line.
+    ///
+    /// The child process needs a piped stdout.
+    Line(Vec<u8>),
+
+    /// Ready once a file of any type exists at the given path.
+    ///
+    /// Relative paths are resolved against [`WaitReady::current_dir`].
+    File(PathBuf),
+
+    /// Ready once a Unix socket exists at the given path.
+    ///
+    /// Relative paths are resolved against [`WaitReady::current_dir`].
+    Socket(PathBuf),
+}
+
+impl Probe {
+    /// Creates a new probe waiting for a stdout line containing the
+    /// given bytes.
+    pub fn line(pattern: impl Into<Vec<u8>>) -> Self {
+        Self::Line(pattern.into())
+    }
+
+    /// Creates a new probe waiting for a file at the given path.
+    pub fn file(path: impl Into<PathBuf>) -> Self {
+        Self::File(path.into())
+    }
+
+    /// Creates a new probe waiting for a Unix socket at the given
+    /// path.
+    pub fn socket(path: impl Into<PathBuf>) -> Self {
+        Self::Socket(path.into())
+    }
+
+    /// Returns `true` if the given type of the probed path, if any,
+    /// satisfies the probe.
+    fn matches(&self, file_type: Option<FileType>) -> bool {
+        match (self, file_type) {
+            (Self::File(_), Some(_)) => true,
+            (Self::Socket(_), Some(file_type)) => file_type.is_socket(),
+            _ => false,
+        }
+    }
+}
+
+/// The output of the [`WaitReady`] coroutine.
+#[derive(Debug)]
+pub enum WaitReadyOutput {
+    /// The probe succeeded: the child process is ready.
+    ///
+    /// The bytes read from stdout after the matching line, if any,
+    /// are given back along with the child process.
+    Ready(Child, Vec<u8>),
+
+    /// The child process exited before being ready.
+    Exited(SpawnOutput),
+
+    /// The probe did not succeed before the timeout: the child
+    /// process has been terminated.
+    Timeout(TerminateOutput),
+}
+
+/// The I/O-free coroutine for waiting for a spawned child process to
+/// be ready, according to a [`Probe`].
+///
+/// This coroutine should be used for services, like local helper
+/// servers, which need some time to start before being usable. The
+/// child process is usually obtained from [`super::Spawn`].
+///
+/// Stdout is read while waiting for a line, and bytes following the
+/// matching line are given back once ready. Paths are checked
+/// periodically, using [`Io::Stat`] requests, while waiting for the
+/// child process to exit in between.
+///
+/// If the probe does not succeed before the timeout, the child process
+/// is terminated using [`Terminate`]. While waiting, the child process
+/// is killed on drop (see [`Child::kill_on_drop`]), so that it does
+/// not outlive a failed I/O nor a dropped coroutine.
+#[derive(Debug)]
+pub struct WaitReady {
+    child: Option<Child>,
+    kill_on_drop: bool,
+    probe: Probe,
+    current_dir: Option<PathBuf>,
+    timeout: Duration,
+    interval: Duration,
+    policy: TerminationPolicy,
+    deadline: Option<Instant>,
+    buffer: Vec<u8>,
+    eof: bool,
+    stat: bool,
+    terminate: Option<Terminate>,
+}
+
+impl WaitReady {
+    /// Creates a new coroutine from the given child process, probe
+    /// and timeout.
+    pub fn new(mut child: Child, probe: Probe, timeout: Duration) -> Self {
+        debug!("prepare child {} to be probed: {probe:?}", child.id());
+        let kill_on_drop = mem::replace(&mut child.kill_on_drop, true);
+
+        Self {
+            child: Some(child),
+            kill_on_drop,
+            probe,
+            current_dir: None,
+            timeout,
+            interval: Duration::from_millis(50),
+            policy: TerminationPolicy::default(),
+            deadline: None,
+            buffer: Vec::new(),
+            eof: false,
+            stat: true,
+            terminate: None,
+        }
+    }
+
+    /// Sets the working directory of the child process, against
+    /// which relative probe paths are resolved.
+    ///
+    /// This should match the [`crate::Command::current_dir`] the
+    /// child process was spawned with. Relative paths are resolved
+    /// against the working directory of the current process
+    /// otherwise.
+    pub fn current_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.current_dir = Some(dir.into());
+        self
+    }
+
+    /// Sets the interval between two checks of a path, 50 ms by
+    /// default.
+    pub fn interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// Sets the policy used to terminate the child process on
+    /// timeout.
+    pub fn termination(mut self, policy: TerminationPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// Makes the coroutine progress.
+    pub fn resume(&mut self, input: Option<Io>) -> Result<WaitReadyOutput, Io> {
+        if let Some(terminate) = &mut self.terminate {
+            return terminate.resume(input).map(WaitReadyOutput::Timeout);
+        }
+
+        match input {
+            None => (),
+            Some(Io::Read(Ok((child, output)))) => {
+                match output {
+                    ReadOutput::Chunk(chunk) if chunk.stream == Stream::Stdout => {
+                        debug!("read {} bytes from stdout", chunk.bytes.len());
+                        self.buffer.extend(chunk.bytes);
+
+                        if let Probe::Line(pattern) = &self.probe {
+                            if find_line(&mut self.buffer, pattern) {
+                                return Ok(self.ready(child));
+                            }
+                        }
+                    }
+                    ReadOutput::Chunk(_) => (),
+                    ReadOutput::Eof => {
+                        debug!("reached end of file before child was ready");
+                        self.eof = true;
+                    }
+                    ReadOutput::Timeout => (),
+                }
+
+                self.child = Some(child);
+            }
+            Some(Io::Stat(Ok(file_type))) if self.probe.matches(file_type) => {
+                let Some(child) = self.child.take() else {
+                    return Err(Io::UnavailableInput);
+                };
+
+                return Ok(self.ready(child));
+            }
+            Some(Io::Stat(Ok(_))) => (),
+            Some(Io::WaitTimeout(Ok(Ok(output)))) => {
+                debug!("child exited before being ready: {}", output.status);
+                return Ok(WaitReadyOutput::Exited(output));
+            }
+            Some(Io::WaitTimeout(Ok(Err(child)))) => self.child = Some(child),
+            Some(Io::Read(Err(input))) => return Err(Io::Read(Err(input))),
+            Some(Io::Stat(Err(path))) => return Err(Io::Stat(Err(path))),
+            Some(Io::WaitTimeout(Err(input))) => return Err(Io::WaitTimeout(Err(input))),
+            Some(input) => return Err(Io::UnexpectedInput(Box::new(input))),
+        }
+
+        let Some(child) = self.child.take() else {
+            return Err(Io::UnavailableInput);
+        };
+
+        let now = Instant::now();
+        let deadline = *self.deadline.get_or_insert(now + self.timeout);
+
+        if deadline <= now {
+            debug!("child {} not ready before timeout", child.id());
+            let mut terminate = Terminate::new(child, self.policy.clone());
+            let output = terminate.resume(None).map(WaitReadyOutput::Timeout);
+            self.terminate = Some(terminate);
+            return output;
+        }
+
+        let timeout = deadline - now;
+
+        match &self.probe {
+            Probe::Line(_) if !self.eof => {
+                debug!("need to read stdout of child {}", child.id());
+                Err(Io::Read(Err((child, Some(timeout)))))
+            }
+            // stdout is closed, the child process is likely exiting
+            Probe::Line(_) => Err(Io::WaitTimeout(Err((child, timeout)))),
+            Probe::File(path) | Probe::Socket(path) if self.stat => {
+                let path = match &self.current_dir {
+                    Some(dir) => dir.join(path),
+                    None => path.clone(),
+                };
+
+                debug!("need to check path {}", path.display());
+                self.stat = false;
+                self.child = Some(child);
+                Err(Io::Stat(Err(path)))
+            }
+            Probe::File(_) | Probe::Socket(_) => {
+                self.stat = true;
+                Err(Io::WaitTimeout(Err((child, timeout.min(self.interval)))))
+            }
+        }
+    }
+
+    fn ready(&mut self, mut child: Child) -> WaitReadyOutput {
+        debug!("child {} is ready", child.id());
+        child.kill_on_drop = self.kill_on_drop;
+        WaitReadyOutput::Ready(child, mem::take(&mut self.buffer))
+    }
+}
+
+/// Consumes the complete lines of the given buffer until one of them
+/// contains the given pattern, then returns `true`.
+fn find_line(buffer: &mut Vec<u8>, pattern: &[u8]) -> bool {
+    while let Some(end) = buffer.iter().position(|byte| *byte == b'\n') {
+        let line: Vec<u8> = buffer.drain(..=end).collect();
+
+        if pattern.is_empty() || line.windows(pattern.len()).any(|window| window == pattern) {
+            return true;
+        }
+    }
+
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::find_line;
+
+    #[test]
+    fn find_line_in_chunks() {
+        let mut buffer = b"starting\nlisten".to_vec();
+        assert!(!find_line(&mut buffer, b"listening on"));
+        assert_eq!(b"listen", buffer.as_slice());
+
+        buffer.extend(b"ing on :8080");
+        assert!(!find_line(&mut buffer, b"listening on"));
+
+        buffer.extend(b"\nrest");
+        assert!(find_line(&mut buffer, b"listening on"));
+        assert_eq!(b"rest", buffer.as_slice());
+    }
+}
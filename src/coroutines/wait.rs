@@ -0,0 +1,48 @@
+//! Module dedicated to the I/O-free [`Wait`] coroutine.
+
+use log::debug;
+
+use crate::{Child, Io, SpawnOutput};
+
+/// The I/O-free coroutine for waiting for a spawned child process'
+/// exit status.
+///
+/// The child process is usually obtained from [`super::Spawn`].
+#[derive(Debug)]
+pub struct Wait {
+    child: Option<Child>,
+}
+
+impl Wait {
+    /// Creates a new coroutine from the given child process.
+    pub fn new(child: Child) -> Self {
+        debug!("prepare child {} to be waited", child.id());
+        let child = Some(child);
+        Self { child }
+    }
+
+    /// Makes the coroutine progress.
+    pub fn resume(&mut self, input: Option<Io>) -> Result<SpawnOutput, Io> {
+        let Some(input) = input else {
+            return Err(match self.child.take() {
+                Some(child) => Io::Wait(Err(child)),
+                None => Io::UnavailableInput,
+            });
+        };
+
+        let Io::Wait(output) = input else {
+            return Err(Io::UnexpectedInput(Box::new(input)));
+        };
+
+        match output {
+            Ok(output) => {
+                debug!("successfully waited child: {output:?}");
+                Ok(output)
+            }
+            Err(child) => {
+                debug!("need to wait child");
+                Err(Io::Wait(Err(child)))
+            }
+        }
+    }
+}
@@ -0,0 +1,311 @@
+//! Module dedicated to the I/O-free [`Xargs`] coroutine.
+
+use std::{collections::VecDeque, ffi::OsString, fmt, mem, process::ExitStatus};
+
+use log::debug;
+
+use crate::{Command, Io, Output};
+
+use super::JobPool;
+
+/// The output of the [`Xargs`] coroutine.
+#[derive(Debug)]
+pub struct XargsOutput {
+    /// The arguments of each batch, in order.
+    pub batches: Vec<Vec<OsString>>,
+
+    /// The output of each batch, in order.
+    pub outputs: Vec<Output>,
+
+    /// The arguments exceeding the size limit of a single argument
+    /// (see [`MAX_ARG_STRLEN`]), which were not passed to any batch.
+    pub rejected: Vec<OsString>,
+}
+
+impl XargsOutput {
+    /// Returns `true` if all the batches succeeded, and no argument
+    /// was rejected.
+    pub fn success(&self) -> bool {
+        self.rejected.is_empty() && self.outputs.iter().all(|output| output.status.success())
+    }
+
+    /// Returns the exit status of each batch, in order.
+    pub fn statuses(&self) -> Vec<ExitStatus> {
+        self.outputs.iter().map(|output| output.status).collect()
+    }
+
+    /// Returns the concatenated stdout of all batches, in order.
+    pub fn stdout(&self) -> Vec<u8> {
+        self.outputs
+            .iter()
+            .flat_map(|o| &o.stdout)
+            .copied()
+            .collect()
+    }
+
+    /// Returns the concatenated stderr of all batches, in order.
+    pub fn stderr(&self) -> Vec<u8> {
+        self.outputs
+            .iter()
+            .flat_map(|o| &o.stderr)
+            .copied()
+            .collect()
+    }
+}
+
+/// The size limit of a single argument, including its nul
+/// terminator, on Linux.
+///
+/// The kernel limit is 32 pages, assumed to be 4 KiB each: larger
+/// pages allow larger arguments, which are still rejected.
+pub const MAX_ARG_STRLEN: usize = 32 * 4096;
+
+/// The I/O-free coroutine for running a command over a large list of
+/// arguments, split into batches.
+///
+/// Like `xargs`, this coroutine appends as many arguments as
+/// possible to the base command without exceeding the system
+/// argument size limit (`ARG_MAX`), which is requested to the runtime
+/// using [`Io::ArgMax`] unless [`Xargs::max_bytes`] is set. The
+/// number of arguments per batch can also be capped with
+/// [`Xargs::max_args`]. On Linux, arguments exceeding
+/// [`MAX_ARG_STRLEN`] cannot be passed to any process: they are
+/// reported in [`XargsOutput::rejected`] instead.
+///
+/// Batches run sequentially by default, or concurrently using
+/// [`Xargs::parallel`]. Their output is captured like
+/// [`super::JobPool`] does, and aggregated once all batches
+/// completed. Each batch runs a new base command, built by the given
+/// function (see [`Command`]'s [`Clone`] implementation for why).
+pub struct Xargs {
+    command: Box<dyn Fn() -> Command + Send>,
+    args: VecDeque<OsString>,
+    max_args: Option<usize>,
+    max_bytes: Option<usize>,
+    parallel: usize,
+    pool: Option<JobPool>,
+    batches: Vec<Vec<OsString>>,
+    outputs: Vec<Option<Output>>,
+    rejected: Vec<OsString>,
+    done: bool,
+}
+
+impl Xargs {
+    /// Creates a new coroutine from the given function building the
+    /// base command, and the given extra arguments.
+    pub fn new<F, I, S>(command: F, args: I) -> Self
+    where
+        F: Fn() -> Command + Send + 'static,
+        I: IntoIterator<Item = S>,
+        S: Into<OsString>,
+    {
+        let args: VecDeque<_> = args.into_iter().map(Into::into).collect();
+        debug!("prepare {} arguments to be batched", args.len());
+
+        Self {
+            command: Box::new(command),
+            args,
+            max_args: None,
+            max_bytes: None,
+            parallel: 1,
+            pool: None,
+            batches: Vec::new(),
+            outputs: Vec::new(),
+            rejected: Vec::new(),
+            done: false,
+        }
+    }
+
+    /// Caps the number of extra arguments per batch.
+    pub fn max_args(mut self, max: usize) -> Self {
+        self.max_args = Some(max.max(1));
+        self
+    }
+
+    /// Sets the argument size limit, in bytes, instead of requesting
+    /// the system one.
+    pub fn max_bytes(mut self, max: usize) -> Self {
+        self.max_bytes = Some(max);
+        self
+    }
+
+    /// Sets the number of batches running concurrently.
+    pub fn parallel(mut self, max: usize) -> Self {
+        self.parallel = max.max(1);
+        self
+    }
+
+    /// Makes the coroutine progress.
+    pub fn resume(&mut self, mut input: Option<Io>) -> Result<XargsOutput, Io> {
+        if self.done {
+            return Err(Io::UnavailableInput);
+        }
+
+        if self.pool.is_none() {
+            let max_bytes = match (self.max_bytes, input.take()) {
+                (Some(max), _) => max,
+                (None, Some(Io::ArgMax(Ok(max)))) => max,
+                (None, Some(Io::ArgMax(Err(())))) => return Err(Io::ArgMax(Err(()))),
+                (None, Some(input)) => return Err(Io::UnexpectedInput(Box::new(input))),
+                (None, None) => {
+                    debug!("need to request the argument size limit");
+                    return Err(Io::ArgMax(Err(())));
+                }
+            };
+
+            self.batches = self.split(max_bytes);
+            self.outputs = self.batches.iter().map(|_| None).collect();
+            debug!("split arguments into {} batches", self.batches.len());
+
+            let commands = self.batches.iter().map(|args| {
+                let mut command = (self.command)();
+                command.args(args);
+                command
+            });
+
+            self.pool = Some(JobPool::new(commands, self.parallel));
+        }
+
+        let Some(pool) = self.pool.as_mut() else {
+            return Err(Io::UnavailableInput);
+        };
+
+        while let Some((index, output)) = pool.resume(input.take())? {
+            debug!("batch {index} completed with {}", output.status);
+            self.outputs[index] = Some(output);
+        }
+
+        let outputs = mem::take(&mut self.outputs).into_iter().flatten().collect();
+        let batches = mem::take(&mut self.batches);
+        let rejected = mem::take(&mut self.rejected);
+        self.done = true;
+
+        Ok(XargsOutput {
+            batches,
+            outputs,
+            rejected,
+        })
+    }
+
+    /// Splits the extra arguments into batches fitting both the
+    /// given size limit and the maximum number of arguments.
+    fn split(&mut self, max_bytes: usize) -> Vec<Vec<OsString>> {
+        let command = (self.command)();
+        let base = arg_size(&command.program)
+            + command.args.iter().flatten().map(arg_size).sum::<usize>()
+            + command
+                .envs
+                .iter()
+                .flatten()
+                .map(|(key, val)| arg_size(key) + val.len() + 1)
+                .sum::<usize>();
+
+        let max_args = self.max_args.unwrap_or(usize::MAX);
+        let mut batches = Vec::new();
+        let mut batch = Vec::new();
+        let mut size = base;
+
+        while let Some(arg) = self.args.pop_front() {
+            if cfg!(target_os = "linux") && arg.len() >= MAX_ARG_STRLEN {
+                debug!("reject argument of {} bytes", arg.len());
+                self.rejected.push(arg);
+                continue;
+            }
+
+            let arg_size = arg_size(&arg);
+            let full = batch.len() >= max_args || size + arg_size > max_bytes;
+
+            // an argument too large to fit any batch still runs alone
+            if full && !batch.is_empty() {
+                batches.push(mem::take(&mut batch));
+                size = base;
+            }
+
+            size += arg_size;
+            batch.push(arg);
+        }
+
+        if !batch.is_empty() {
+            batches.push(batch);
+        }
+
+        batches
+    }
+}
+
+impl fmt::Debug for Xargs {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Xargs")
+            .field("args", &self.args)
+            .field("max_args", &self.max_args)
+            .field("max_bytes", &self.max_bytes)
+            .field("parallel", &self.parallel)
+            .field("pool", &self.pool)
+            .field("batches", &self.batches)
+            .field("rejected", &self.rejected)
+            .field("done", &self.done)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Returns the space taken by the given argument in the argument
+/// block of a new process: the bytes, the nul terminator and the
+/// pointer to it.
+fn arg_size(arg: impl AsRef<std::ffi::OsStr>) -> usize {
+    arg.as_ref().len() + 1 + mem::size_of::<usize>()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{ffi::OsString, mem};
+
+    use crate::{Command, Io};
+
+    use super::{Xargs, MAX_ARG_STRLEN};
+
+    #[test]
+    fn split() {
+        let args = ["a", "bb", "ccc", "dddd", "e"];
+
+        let mut xargs = Xargs::new(|| Command::new("echo"), args).max_args(2);
+        let batches = xargs.split(usize::MAX);
+        assert_eq!(
+            vec![vec!["a", "bb"], vec!["ccc", "dddd"], vec!["e"]],
+            batches
+        );
+
+        // each argument takes its length, its nul terminator and its
+        // pointer
+        let ptr = mem::size_of::<usize>();
+        let mut xargs = Xargs::new(|| Command::new("echo"), args);
+        let batches = xargs.split((5 + ptr) + (2 + ptr) + (3 + ptr));
+        assert_eq!(
+            vec![vec!["a", "bb"], vec!["ccc"], vec!["dddd"], vec!["e"]],
+            batches
+        );
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn reject() {
+        let long = "a".repeat(MAX_ARG_STRLEN);
+        let args = ["a", long.as_str(), "b"];
+
+        let mut xargs = Xargs::new(|| Command::new("echo"), args);
+        let batches = xargs.split(usize::MAX);
+        assert_eq!(vec![vec!["a", "b"]], batches);
+        assert_eq!(vec![OsString::from(long)], xargs.rejected);
+    }
+
+    #[test]
+    fn resume_after_completion() {
+        let args: [&str; 0] = [];
+        let mut xargs = Xargs::new(|| Command::new("echo"), args).max_bytes(1024);
+
+        let output = xargs.resume(None).unwrap();
+        assert!(output.success());
+        assert!(output.batches.is_empty());
+
+        assert!(matches!(xargs.resume(None), Err(Io::UnavailableInput)));
+    }
+}
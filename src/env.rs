@@ -0,0 +1,50 @@
+//! Module dedicated to environment inheritance.
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// The policy deciding which environment variables of the parent
+/// process a child process inherits.
+///
+/// Variables explicitly set with [`crate::Command::env`] are always
+/// passed to the child process, on top of inherited ones.
+#[derive(Clone, Debug, Default, Eq, Hash, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Deserialize, Serialize),
+    serde(rename_all = "kebab-case")
+)]
+pub enum EnvInherit {
+    /// Inherits all the variables of the parent process.
+    #[default]
+    All,
+
+    /// Inherits none of the variables of the parent process.
+    None,
+
+    /// Inherits only the given variables of the parent process, when
+    /// they are defined.
+    Allowlist(Vec<String>),
+}
+
+impl EnvInherit {
+    /// The variables commonly needed by programs, without exposing
+    /// secrets: `HOME`, `LANG`, `LC_ALL`, `PATH`, `TERM`, `TZ` and
+    /// `USER`.
+    pub const COMMON: [&'static str; 7] = ["HOME", "LANG", "LC_ALL", "PATH", "TERM", "TZ", "USER"];
+
+    /// Creates a new policy inheriting only the given variables.
+    pub fn allowlist<I, S>(keys: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        Self::Allowlist(keys.into_iter().map(Into::into).collect())
+    }
+
+    /// Creates a new policy inheriting only [`EnvInherit::COMMON`]
+    /// variables.
+    pub fn common() -> Self {
+        Self::allowlist(Self::COMMON)
+    }
+}
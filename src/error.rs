@@ -0,0 +1,130 @@
+//! Module dedicated to process, capture and template errors.
+
+use std::{error, ffi::OsString, fmt, io, path::PathBuf};
+
+use crate::Stream;
+
+/// The error returned by runtimes when a process cannot be spawned.
+///
+/// Runtimes return [`io::Error`]s, which wrap this error when the
+/// cause of the failure is known. It can be recovered using
+/// [`io::Error::get_ref`] then [`error::Error::downcast_ref`].
+#[derive(Debug)]
+pub enum SpawnError {
+    /// The working directory of the child process does not exist.
+    CurrentDirNotFound(PathBuf),
+
+    /// The working directory of the child process exists but is not
+    /// a directory.
+    CurrentDirNotADirectory(PathBuf),
+
+    /// The program of the child process could not be found.
+    ProgramNotFound(OsString),
+
+    /// The file at the given path, redirected from or to a standard
+    /// stream of the child process, could not be opened.
+    Redirect(PathBuf, io::Error),
+}
+
+impl fmt::Display for SpawnError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::CurrentDirNotFound(path) => {
+                write!(f, "cannot find working directory {}", path.display())
+            }
+            Self::CurrentDirNotADirectory(path) => {
+                write!(f, "working directory {} is not a directory", path.display())
+            }
+            Self::ProgramNotFound(program) => {
+                write!(f, "cannot find program {}", program.to_string_lossy())
+            }
+            Self::Redirect(path, _) => {
+                write!(f, "cannot open redirected file {}", path.display())
+            }
+        }
+    }
+}
+
+impl error::Error for SpawnError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Self::Redirect(_, err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<SpawnError> for io::Error {
+    fn from(err: SpawnError) -> Self {
+        let kind = match &err {
+            SpawnError::CurrentDirNotFound(_) => io::ErrorKind::NotFound,
+            SpawnError::CurrentDirNotADirectory(_) => io::ErrorKind::NotADirectory,
+            SpawnError::ProgramNotFound(_) => io::ErrorKind::NotFound,
+            SpawnError::Redirect(_, err) => err.kind(),
+        };
+
+        io::Error::new(kind, err)
+    }
+}
+
+/// The error returned by runtimes when the output of a process
+/// cannot be captured.
+///
+/// Like [`SpawnError`], it is wrapped in an [`io::Error`].
+#[derive(Debug)]
+pub enum CaptureError {
+    /// A stream exceeded its limit, with the
+    /// [`crate::OverflowPolicy::Error`] policy. The child process
+    /// has been killed.
+    LimitExceeded(Stream, usize),
+}
+
+impl fmt::Display for CaptureError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::LimitExceeded(stream, max) => {
+                let stream = match stream {
+                    Stream::Stdout => "stdout",
+                    Stream::Stderr => "stderr",
+                };
+                write!(f, "{stream} exceeded its limit of {max} bytes")
+            }
+        }
+    }
+}
+
+impl error::Error for CaptureError {}
+
+impl From<CaptureError> for io::Error {
+    fn from(err: CaptureError) -> Self {
+        io::Error::other(err)
+    }
+}
+
+/// The error returned when a [`crate::CommandTemplate`] cannot be
+/// rendered.
+#[derive(Debug)]
+pub enum TemplateError {
+    /// A placeholder has no value.
+    UnknownPlaceholder(String),
+
+    /// An environment variable is not defined.
+    UndefinedVariable(String),
+
+    /// A placeholder or a variable is missing its closing brace.
+    UnclosedPlaceholder(String),
+}
+
+impl fmt::Display for TemplateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnknownPlaceholder(name) => write!(f, "unknown placeholder {{{name}}}"),
+            Self::UndefinedVariable(name) => write!(f, "undefined variable ${name}"),
+            Self::UnclosedPlaceholder(template) => {
+                write!(f, "missing closing brace in {template}")
+            }
+        }
+    }
+}
+
+impl error::Error for TemplateError {}
@@ -1,6 +1,12 @@
-use std::process::Output;
+use std::time::Duration;
+#[cfg(unix)]
+use std::{fs::FileType, path::PathBuf};
 
-use crate::{Command, SpawnOutput};
+use crate::{Capture, CaptureOutput, Child, Command, Output, OutputLimit, SpawnOutput};
+#[cfg(unix)]
+use crate::{Job, ReadOutput, Signal};
+#[cfg(target_os = "linux")]
+use crate::{Pty, WindowSize};
 
 /// The process I/O request enum, emitted by flows and processed by
 /// handlers.
@@ -34,10 +40,146 @@ pub enum Io {
     /// This variant requires I/O connectors to take the command
     /// builder from the flow using [`take_command`], spawn a process
     /// then give the child process' [`Output`] to the flow using
-    /// [`set_output`].
+    /// [`set_output`]. The output of stdout and stderr is bounded by
+    /// their respective optional [`OutputLimit`].
     ///
     /// [`take_command`]: crate::State::take_command
-    /// [`Output`]: std::process::Output
     /// [`set_output`]: crate::State::set_output
-    SpawnThenWaitWithOutput(Result<Output, Command>),
+    SpawnThenWaitWithOutput(Result<Output, (Command, Option<OutputLimit>, Option<OutputLimit>)>),
+
+    /// I/O for spawning a process and capturing its output, with
+    /// bounded memory.
+    ///
+    /// This variant requires I/O connectors to take the command
+    /// builder and the [`Capture`] configuration from the coroutine,
+    /// spawn a process with piped stdout and stderr, read both
+    /// streams while enforcing their limit, wait for the exit status
+    /// then give the resulting [`CaptureOutput`] back to the
+    /// coroutine.
+    SpawnThenCapture(Result<CaptureOutput, (Command, Capture)>),
+
+    /// I/O for sleeping, for example between two attempts of a
+    /// command.
+    ///
+    /// This variant requires I/O connectors to take the duration
+    /// from the coroutine, sleep for that duration, then give the
+    /// unit back to the coroutine.
+    Sleep(Result<(), Duration>),
+
+    /// I/O for spawning a process without waiting for it.
+    ///
+    /// This variant requires I/O connectors to take the command
+    /// builder from the coroutine, spawn a process then give the
+    /// [`Child`] handle back to the coroutine.
+    Spawn(Result<Child, Command>),
+
+    /// I/O for spawning a detached process, which outlives its
+    /// parent.
+    ///
+    /// This variant requires I/O connectors to take the command
+    /// builder from the coroutine, spawn a process in a new session
+    /// that forks again then exits straight away, so that the
+    /// grandchild is reparented. Standard streams not configured are
+    /// redirected to `/dev/null`. The ID of the grandchild is then
+    /// given back to the coroutine.
+    #[cfg(unix)]
+    SpawnDetached(Result<u32, Command>),
+
+    /// I/O for waiting for a spawned child process' exit status.
+    ///
+    /// This variant requires I/O connectors to take the [`Child`]
+    /// handle from the coroutine, collect its std{in,out,err}, wait
+    /// for its exit status then give the resulting [`SpawnOutput`]
+    /// back to the coroutine.
+    Wait(Result<SpawnOutput, Child>),
+
+    /// I/O for sending a signal to a spawned child process.
+    ///
+    /// This variant requires I/O connectors to take the [`Child`]
+    /// handle and the [`Signal`] from the coroutine, send the signal
+    /// to the child process (or to its process group, if it leads
+    /// one) then give the [`Child`] handle back to the coroutine.
+    #[cfg(unix)]
+    Kill(Result<Child, (Child, Signal)>),
+
+    /// I/O for waiting for a spawned child process' exit status, for
+    /// at most the given duration.
+    ///
+    /// This variant requires I/O connectors to take the [`Child`]
+    /// handle and the timeout from the coroutine, then wait for the
+    /// child process to exit. If it exits before the timeout, its
+    /// resulting [`SpawnOutput`] is given back to the coroutine, like
+    /// [`Io::Wait`]. Otherwise, the [`Child`] handle is given back.
+    #[cfg(unix)]
+    WaitTimeout(Result<Result<SpawnOutput, Child>, (Child, Duration)>),
+
+    /// I/O for spawning a process in a new pseudo-terminal.
+    ///
+    /// This variant requires I/O connectors to take the command
+    /// builder and the initial [`WindowSize`] from the coroutine,
+    /// allocate a pseudo-terminal pair, spawn a process in a new
+    /// session whose controlling terminal is the slave side, then
+    /// give the resulting [`Pty`] back to the coroutine.
+    #[cfg(target_os = "linux")]
+    SpawnPty(Result<Pty, (Command, WindowSize)>),
+
+    /// I/O for resizing the window of a pseudo-terminal.
+    ///
+    /// This variant requires I/O connectors to take the [`Pty`] and
+    /// its new [`WindowSize`] from the coroutine, resize the window
+    /// then give the [`Pty`] back to the coroutine.
+    #[cfg(target_os = "linux")]
+    ResizePty(Result<Pty, (Pty, WindowSize)>),
+
+    /// I/O for reading from a spawned child process' output streams.
+    ///
+    /// This variant requires I/O connectors to take the [`Child`]
+    /// handle and the optional timeout from the coroutine, wait for
+    /// bytes on either the piped stdout or stderr of the child
+    /// process, then give the [`Child`] handle back to the coroutine
+    /// along with the [`ReadOutput`].
+    ///
+    /// Streams reaching the end of file are closed, so that they are
+    /// not read anymore.
+    #[cfg(unix)]
+    Read(Result<(Child, ReadOutput), (Child, Option<Duration>)>),
+
+    /// I/O for writing bytes to a spawned child process' stdin.
+    ///
+    /// This variant requires I/O connectors to take the [`Child`]
+    /// handle and the bytes from the coroutine, write all the bytes
+    /// to the piped stdin of the child process, then give the
+    /// [`Child`] handle back to the coroutine.
+    #[cfg(unix)]
+    Write(Result<Child, (Child, Vec<u8>)>),
+
+    /// I/O for waiting for any of the given running jobs.
+    ///
+    /// This variant requires I/O connectors to take the running
+    /// [`Job`]s from the coroutine, collect the piped stdout and
+    /// stderr of all of them, until one job closes its streams and
+    /// exits. The remaining jobs are then given back to the
+    /// coroutine, along with the index and the [`Output`] of the
+    /// completed job.
+    #[cfg(unix)]
+    WaitAny(Result<(Vec<Job>, usize, Output), Vec<Job>>),
+
+    /// I/O for querying the type of the file at the given path.
+    ///
+    /// This variant requires I/O connectors to take the path from the
+    /// coroutine, query the metadata of the file it points to, then
+    /// give its [`FileType`] back to the coroutine, or `None` if the
+    /// file does not exist.
+    #[cfg(unix)]
+    Stat(Result<Option<FileType>, PathBuf>),
+
+    /// I/O for requesting the space available for the arguments of
+    /// a new process, in bytes.
+    ///
+    /// This variant requires I/O connectors to compute the system
+    /// argument size limit (`ARG_MAX`) minus the space taken by the
+    /// environment inherited by child processes, then give it back to
+    /// the coroutine.
+    #[cfg(unix)]
+    ArgMax(Result<usize, ()>),
 }
@@ -1,13 +1,43 @@
 #![cfg_attr(docsrs, feature(doc_cfg, doc_auto_cfg))]
 #![doc = include_str!("../README.md")]
+// Coroutines return I/O requests as errors, which are large by design.
+#![allow(clippy::result_large_err)]
 
+mod capture;
+mod child;
 mod command;
 pub mod coroutines;
+mod env;
+mod error;
 mod io;
 mod output;
+#[cfg(target_os = "linux")]
+mod pty;
+mod redirect;
+mod rlimit;
 pub mod runtimes;
 #[cfg(feature = "serde")]
 mod serde;
+#[cfg(unix)]
+mod signal;
+mod template;
 
+#[cfg(target_os = "linux")]
 #[doc(inline)]
-pub use self::{command::Command, io::Io, output::SpawnOutput};
+pub use self::pty::{Pty, WindowSize};
+#[cfg(unix)]
+#[doc(inline)]
+pub use self::signal::Signal;
+#[doc(inline)]
+pub use self::{
+    capture::{Capture, CaptureMode, CaptureOutput, Captured, OutputLimit, OverflowPolicy, Tee},
+    child::{Child, Job},
+    command::Command,
+    env::EnvInherit,
+    error::{CaptureError, SpawnError, TemplateError},
+    io::Io,
+    output::{Chunk, Output, ReadOutput, SpawnOutput, Stream, Usage},
+    redirect::Redirect,
+    rlimit::{Rlimit, RlimitResource},
+    template::CommandTemplate,
+};
@@ -1,4 +1,7 @@
-use std::process::{ExitStatus, Stdio};
+use std::{
+    process::{ExitStatus, Stdio},
+    time::{Duration, SystemTime},
+};
 
 #[derive(Debug)]
 pub struct SpawnOutput {
@@ -6,4 +9,108 @@ pub struct SpawnOutput {
     pub stdin: Option<Stdio>,
     pub stdout: Option<Stdio>,
     pub stderr: Option<Stdio>,
+
+    /// The timing and resource usage of the child process.
+    pub usage: Usage,
+}
+
+/// The output of a finished child process, along with its resource
+/// usage.
+///
+/// This is the counterpart of [`std::process::Output`], returned by
+/// [`crate::coroutines::SpawnThenWaitWithOutput`] and
+/// [`crate::coroutines::JobPool`].
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Output {
+    /// The exit status of the child process.
+    pub status: ExitStatus,
+
+    /// The bytes collected from the child process' stdout.
+    pub stdout: Vec<u8>,
+
+    /// The bytes collected from the child process' stderr.
+    pub stderr: Vec<u8>,
+
+    /// The number of bytes discarded from stdout because of its
+    /// limit, see
+    /// [`crate::coroutines::SpawnThenWaitWithOutput::stdout_limit`].
+    pub stdout_discarded: u64,
+
+    /// The number of bytes discarded from stderr because of its
+    /// limit, see
+    /// [`crate::coroutines::SpawnThenWaitWithOutput::stderr_limit`].
+    pub stderr_discarded: u64,
+
+    /// The timing and resource usage of the child process.
+    pub usage: Usage,
+}
+
+impl Output {
+    /// Returns `true` if bytes have been discarded from stdout or
+    /// stderr.
+    pub fn is_truncated(&self) -> bool {
+        self.stdout_discarded > 0 || self.stderr_discarded > 0
+    }
+}
+
+/// The timing and resource usage of a terminated child process.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Usage {
+    /// The time the child process was spawned at.
+    pub started: SystemTime,
+
+    /// The time the child process was waited at.
+    pub ended: SystemTime,
+
+    /// The wall-clock duration between spawn and wait, measured
+    /// with a monotonic clock.
+    pub duration: Duration,
+
+    /// The time spent by the child process in user mode.
+    ///
+    /// Only available on Linux, where it is obtained via `waitid(2)`.
+    pub user_time: Option<Duration>,
+
+    /// The time spent by the child process in kernel mode.
+    ///
+    /// Only available on Linux, where it is obtained via `waitid(2)`.
+    pub system_time: Option<Duration>,
+
+    /// The maximum resident set size of the child process, in bytes.
+    ///
+    /// Only available on Linux, where it is obtained via `waitid(2)`.
+    pub max_rss: Option<u64>,
+}
+
+/// The standard output stream of a child process.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Stream {
+    Stdout,
+    Stderr,
+}
+
+/// The chunk of bytes read from a child process' output stream.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Chunk {
+    /// The stream the bytes come from.
+    pub stream: Stream,
+
+    /// The bytes read.
+    pub bytes: Vec<u8>,
+}
+
+/// The output of a read from a child process' output streams.
+///
+/// See [`crate::Io::Read`].
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum ReadOutput {
+    /// Bytes have been read from one of the streams.
+    Chunk(Chunk),
+
+    /// All the streams reached the end of file: the child process
+    /// closed them, usually because it exited.
+    Eof,
+
+    /// Nothing could be read before the timeout.
+    Timeout,
 }
@@ -0,0 +1,53 @@
+//! Module dedicated to pseudo-terminals ([`Pty`]).
+
+use std::fs::File;
+
+use crate::Child;
+
+/// The size of a pseudo-terminal window, in characters.
+///
+/// Refs: `tty_ioctl(4)`
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct WindowSize {
+    /// Number of rows.
+    pub rows: u16,
+
+    /// Number of columns.
+    pub cols: u16,
+}
+
+impl WindowSize {
+    /// Creates a new window size.
+    pub fn new(rows: u16, cols: u16) -> Self {
+        Self { rows, cols }
+    }
+}
+
+/// The default window size is the one of a standard VT100 terminal,
+/// 24 rows by 80 columns.
+impl Default for WindowSize {
+    fn default() -> Self {
+        Self::new(24, 80)
+    }
+}
+
+/// The child process spawned in a pseudo-terminal.
+///
+/// The child process' std{in,out,err} are connected to the slave
+/// side of the pseudo-terminal, which is also its controlling
+/// terminal. The parent process communicates with the child process
+/// through the master side.
+///
+/// See [`crate::coroutines::SpawnPty`].
+#[derive(Debug)]
+pub struct Pty {
+    /// The master side of the pseudo-terminal.
+    ///
+    /// Reading from it returns what the child process writes to its
+    /// terminal, writing to it simulates terminal input. Once the
+    /// child process exits, reading fails with `EIO`.
+    pub master: File,
+
+    /// The child process, which leads its own session.
+    pub child: Child,
+}
@@ -0,0 +1,32 @@
+//! Module dedicated to declarative std{in,out,err} redirections.
+
+use std::path::PathBuf;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// The declarative redirection of a child process' standard stream.
+///
+/// Unlike [`std::process::Stdio`], a redirection is plain data: files
+/// are opened by I/O connectors at spawn time, relatively to the
+/// working directory of the child process.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Deserialize, Serialize),
+    serde(rename_all = "kebab-case")
+)]
+pub enum Redirect {
+    /// Redirects the stream from or to the null device.
+    Null,
+
+    /// Redirects stdin from the file at the given path, or stdout
+    /// and stderr to it, after creating or truncating it.
+    File(PathBuf),
+
+    /// Redirects stdout and stderr to the end of the file at the
+    /// given path, after creating it if needed.
+    ///
+    /// This redirection behaves like [`Redirect::File`] for stdin.
+    Append(PathBuf),
+}
@@ -0,0 +1,84 @@
+//! Module dedicated to resource limits ([`Rlimit`]) of child
+//! processes.
+//!
+//! Resource limits are only applied on Unix platforms.
+
+/// The resource limited by a [`Rlimit`].
+///
+/// Refs: `setrlimit(2)`
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Deserialize, serde::Serialize),
+    serde(rename_all = "kebab-case")
+)]
+pub enum RlimitResource {
+    /// Maximum size of the process's virtual memory, in bytes
+    /// (`RLIMIT_AS`).
+    AddressSpace,
+
+    /// Maximum size of core files, in bytes (`RLIMIT_CORE`).
+    Core,
+
+    /// Maximum amount of CPU time, in seconds (`RLIMIT_CPU`).
+    Cpu,
+
+    /// Maximum size of the process's data segment, in bytes
+    /// (`RLIMIT_DATA`).
+    Data,
+
+    /// Maximum size of files created by the process, in bytes
+    /// (`RLIMIT_FSIZE`).
+    FileSize,
+
+    /// Maximum number of file descriptors the process can open
+    /// (`RLIMIT_NOFILE`).
+    OpenFiles,
+
+    /// Maximum number of processes of the real user ID of the
+    /// process (`RLIMIT_NPROC`).
+    Processes,
+
+    /// Maximum size of the process stack, in bytes
+    /// (`RLIMIT_STACK`).
+    Stack,
+}
+
+/// The resource limit applied to a child process, before executing
+/// the program.
+///
+/// A limit set to `None` keeps the current limit of the spawning
+/// process, the soft limit being lowered to the hard one if needed.
+/// A limit set to [`u64::MAX`] means unlimited (`RLIM_INFINITY`).
+///
+/// Refs: `setrlimit(2)`
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Deserialize, serde::Serialize),
+    serde(rename_all = "kebab-case", deny_unknown_fields)
+)]
+pub struct Rlimit {
+    /// The limited resource.
+    pub resource: RlimitResource,
+
+    /// The soft limit, which triggers a signal or an error once
+    /// exceeded, depending on the resource.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub soft: Option<u64>,
+
+    /// The hard limit, which acts as a ceiling for the soft limit.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub hard: Option<u64>,
+}
+
+impl Rlimit {
+    /// Creates a new resource limit.
+    pub fn new(resource: RlimitResource, soft: Option<u64>, hard: Option<u64>) -> Self {
+        Self {
+            resource,
+            soft,
+            hard,
+        }
+    }
+}
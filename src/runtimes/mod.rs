@@ -8,7 +8,13 @@
 //! implement your own by taking example on the existing ones. PRs are
 //! welcomed!
 
+#[cfg(any(feature = "std", feature = "tokio"))]
+mod shared;
 #[cfg(feature = "std")]
 pub mod std;
 #[cfg(feature = "tokio")]
 pub mod tokio;
+#[cfg(all(feature = "tracing", any(feature = "std", feature = "tokio")))]
+mod trace;
+#[cfg(all(unix, any(feature = "std", feature = "tokio")))]
+pub(crate) mod unix;
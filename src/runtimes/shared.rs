@@ -0,0 +1,694 @@
+//! Module dedicated to I/O logic shared by runtimes.
+
+use std::{
+    collections::VecDeque,
+    env,
+    ffi::{OsStr, OsString},
+    fs::{self, FileType, OpenOptions},
+    io::{self, Read, Write},
+    path::Path,
+    process::{Child as StdChild, Command as StdCommand, ExitStatus, Stdio},
+    sync::{Mutex, PoisonError},
+    thread,
+    time::{Instant, SystemTime},
+};
+
+#[cfg(feature = "tracing")]
+use super::trace;
+use crate::{
+    Capture, CaptureError, CaptureMode, CaptureOutput, Captured, Child, Chunk, Command, EnvInherit,
+    Output, OutputLimit, OverflowPolicy, Redirect, SpawnError, SpawnOutput, Stream, Tee, Usage,
+};
+#[cfg(unix)]
+use crate::{Job, Signal};
+
+/// Ensures that the working directory of the given command, if any,
+/// exists and is a directory.
+///
+/// Without this check, a missing working directory makes the spawn
+/// fail with the same error as a missing program.
+pub(crate) fn check_current_dir(command: &Command) -> io::Result<()> {
+    let Some(dir) = &command.current_dir else {
+        return Ok(());
+    };
+
+    match fs::metadata(dir) {
+        Ok(metadata) if metadata.is_dir() => Ok(()),
+        Ok(_) => Err(SpawnError::CurrentDirNotADirectory(dir.clone()).into()),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => {
+            Err(SpawnError::CurrentDirNotFound(dir.clone()).into())
+        }
+        Err(err) => Err(err),
+    }
+}
+
+/// Returns the parent process' environment variables inherited by
+/// a child process, according to the given policy.
+///
+/// Returns `None` if the whole environment is inherited, otherwise
+/// the environment of the child process must be cleared before
+/// setting the returned variables.
+pub(crate) fn inherited_envs(policy: &EnvInherit) -> Option<Vec<(&str, OsString)>> {
+    match policy {
+        EnvInherit::All => None,
+        EnvInherit::None => Some(Vec::new()),
+        EnvInherit::Allowlist(keys) => {
+            let vars = keys
+                .iter()
+                .filter_map(|key| Some((key.as_str(), env::var_os(key)?)))
+                .collect();
+            Some(vars)
+        }
+    }
+}
+
+/// Opens the redirections of the given command, if any, replacing
+/// its std{in,out,err} configurations.
+///
+/// Relative paths are resolved against the working directory of the
+/// command, like a shell would do.
+pub(crate) fn open_redirects(command: &mut Command) -> io::Result<()> {
+    let dir = command.current_dir.clone();
+    let dir = dir.as_deref();
+
+    if let Some(redirect) = command.stdin_redirect.take() {
+        command.stdin = Some(open_redirect(dir, redirect, true)?);
+    }
+
+    if let Some(redirect) = command.stdout_redirect.take() {
+        command.stdout = Some(open_redirect(dir, redirect, false)?);
+    }
+
+    if let Some(redirect) = command.stderr_redirect.take() {
+        command.stderr = Some(open_redirect(dir, redirect, false)?);
+    }
+
+    Ok(())
+}
+
+fn open_redirect(dir: Option<&Path>, redirect: Redirect, read: bool) -> io::Result<Stdio> {
+    let (path, append) = match redirect {
+        Redirect::Null => return Ok(Stdio::null()),
+        Redirect::File(path) => (path, false),
+        Redirect::Append(path) => (path, true),
+    };
+
+    let path = match dir {
+        Some(dir) => dir.join(path),
+        None => path,
+    };
+
+    let mut opts = OpenOptions::new();
+
+    if read {
+        opts.read(true);
+    } else if append {
+        opts.append(true).create(true);
+    } else {
+        opts.write(true).create(true).truncate(true);
+    }
+
+    match opts.open(&path) {
+        Ok(file) => Ok(file.into()),
+        Err(err) => Err(SpawnError::Redirect(path, err).into()),
+    }
+}
+
+/// Applies the std{in,out,err} defaults of
+/// [`std::process::Command::output`] to the given command: stdin is
+/// null while stdout and stderr are piped, unless configured.
+pub(crate) fn default_output_stdio(command: &mut Command) {
+    if command.stdin.is_none() {
+        command.stdin(Stdio::null());
+    }
+
+    if command.stdout.is_none() {
+        command.stdout(Stdio::piped());
+    }
+
+    if command.stderr.is_none() {
+        command.stderr(Stdio::piped());
+    }
+}
+
+/// Redirects the std{in,out,err} of the given command to `/dev/null`,
+/// unless configured.
+#[cfg(unix)]
+pub(crate) fn default_null_stdio(command: &mut Command) {
+    if command.stdin.is_none() {
+        command.stdin(Stdio::null());
+    }
+
+    if command.stdout.is_none() {
+        command.stdout(Stdio::null());
+    }
+
+    if command.stderr.is_none() {
+        command.stderr(Stdio::null());
+    }
+}
+
+/// Waits for the given child process, spawned at the given instant,
+/// then returns its exit status and resource usage.
+pub(crate) fn wait(process: &mut StdChild, started: Instant) -> io::Result<(ExitStatus, Usage)> {
+    #[cfg(target_os = "linux")]
+    let (status, rusage) = super::unix::wait(process)?;
+    #[cfg(not(target_os = "linux"))]
+    let status = process.wait()?;
+
+    let duration = started.elapsed();
+    let ended = SystemTime::now();
+
+    #[allow(unused_mut)]
+    let mut usage = Usage {
+        started: ended.checked_sub(duration).unwrap_or(ended),
+        ended,
+        duration,
+        user_time: None,
+        system_time: None,
+        max_rss: None,
+    };
+
+    #[cfg(target_os = "linux")]
+    if let Some(rusage) = rusage {
+        super::unix::set_rusage(&mut usage, &rusage);
+    }
+
+    Ok((status, usage))
+}
+
+/// Collects std{in,out,err} of the given child process, waits for
+/// it, then returns its [`SpawnOutput`].
+pub(crate) fn wait_child(mut child: Child) -> io::Result<SpawnOutput> {
+    let stdin = child.process.stdin.take();
+    let stdout = child.process.stdout.take();
+    let stderr = child.process.stderr.take();
+    let (status, usage) = wait(&mut child.process, child.started)?;
+
+    #[cfg(feature = "tracing")]
+    trace::exited(&child.span, &status, child.started);
+
+    Ok(SpawnOutput {
+        status,
+        stdin: stdin.map(Into::into),
+        stdout: stdout.map(Into::into),
+        stderr: stderr.map(Into::into),
+        usage,
+    })
+}
+
+/// Waits for the child process of the given completed job, then
+/// returns the job index and its [`Output`].
+#[cfg(unix)]
+pub(crate) fn wait_job(job: Job) -> io::Result<(usize, Output)> {
+    let Job {
+        index,
+        mut child,
+        stdout,
+        stderr,
+    } = job;
+
+    let (status, usage) = wait(&mut child.process, child.started)?;
+
+    #[cfg(feature = "tracing")]
+    trace::exited(&child.span, &status, child.started);
+
+    let output = Output {
+        status,
+        stdout,
+        stderr,
+        stdout_discarded: 0,
+        stderr_discarded: 0,
+        usage,
+    };
+
+    Ok((index, output))
+}
+
+/// Returns the type of the file at the given path, or `None` if it
+/// does not exist.
+#[cfg(unix)]
+pub(crate) fn stat(path: &Path) -> io::Result<Option<FileType>> {
+    match fs::metadata(path) {
+        Ok(metadata) => Ok(Some(metadata.file_type())),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(err) => Err(err),
+    }
+}
+
+/// Returns a spawn error mapper, which replaces not found errors by
+/// [`SpawnError::ProgramNotFound`] when the program of the given
+/// command cannot be resolved.
+///
+/// Spawning also fails with a not found error when the program
+/// exists but its interpreter or libraries do not, in which case the
+/// error is left untouched.
+pub(crate) fn map_spawn_error(command: &Command) -> impl FnOnce(io::Error) -> io::Error {
+    let program = command.program.clone();
+    let dir = command.current_dir.clone().unwrap_or_default();
+
+    let path = match command
+        .envs
+        .as_ref()
+        .and_then(|envs| envs.get(OsStr::new("PATH")))
+    {
+        Some(path) => Some(path.clone()),
+        None => match inherited_envs(&command.env_inherit) {
+            None => env::var_os("PATH"),
+            Some(vars) => vars
+                .into_iter()
+                .find_map(|(key, val)| (key == "PATH").then_some(val)),
+        },
+    };
+
+    move |err| {
+        if err.kind() != io::ErrorKind::NotFound || resolves(&program, &dir, path.as_deref()) {
+            return err;
+        }
+
+        SpawnError::ProgramNotFound(program).into()
+    }
+}
+
+/// Returns `true` if the given program exists, either relatively to
+/// the given working directory when it contains a path separator, or
+/// in one of the directories of the given `PATH`.
+fn resolves(program: &OsStr, dir: &Path, path: Option<&OsStr>) -> bool {
+    let program = Path::new(program);
+
+    if program.components().count() > 1 {
+        return dir.join(program).exists();
+    }
+
+    let Some(path) = path else {
+        return false;
+    };
+
+    env::split_paths(path).any(|path| dir.join(path).join(program).is_file())
+}
+
+/// Spawns the given command then captures its output, according to
+/// the given [`Capture`] configuration.
+///
+/// Streams are read concurrently, stderr from a scoped thread, unless
+/// they are merged into a single pipe. The child process is killed
+/// as soon as a stream exceeds a limit with the
+/// [`OverflowPolicy::Error`] policy, and is always waited for.
+pub(crate) fn spawn_then_capture(
+    mut command: StdCommand,
+    capture: Capture,
+    group: Option<i32>,
+) -> io::Result<CaptureOutput> {
+    let merged = match capture.mode {
+        CaptureMode::Merged => {
+            let (reader, writer) = io::pipe()?;
+            command.stdout(writer.try_clone()?);
+            command.stderr(writer);
+            Some(reader)
+        }
+        CaptureMode::Separate | CaptureMode::Tagged => {
+            command.stdout(Stdio::piped());
+            command.stderr(Stdio::piped());
+            None
+        }
+    };
+
+    let mut child = killable(command.spawn()?, group);
+    // releases the write ends of the merged pipe, if any
+    drop(command);
+
+    let started = Instant::now();
+    #[cfg(feature = "tracing")]
+    trace::spawned(&tracing::Span::current(), Some(child.id()));
+
+    let stdout = child.process.stdout.take();
+    let stderr = child.process.stderr.take();
+    let child = Mutex::new(child);
+
+    let stdout_buffer = Mutex::new(match capture.mode {
+        CaptureMode::Tagged => CaptureBuffer::tagged(capture.stdout_limit),
+        _ => CaptureBuffer::new(capture.stdout_limit),
+    });
+    let stderr_buffer = Mutex::new(CaptureBuffer::new(capture.stderr_limit));
+
+    let stderr_into = match capture.mode {
+        CaptureMode::Tagged => &stdout_buffer,
+        _ => &stderr_buffer,
+    };
+
+    let tee = capture.tee.as_ref();
+
+    let result = match merged {
+        Some(reader) => read_streams(
+            Some(reader),
+            stderr,
+            &stdout_buffer,
+            stderr_into,
+            tee,
+            &child,
+        ),
+        None => read_streams(stdout, stderr, &stdout_buffer, stderr_into, tee, &child),
+    };
+
+    let mut child = child.into_inner().unwrap_or_else(PoisonError::into_inner);
+    let (status, usage) = wait(&mut child.process, started)?;
+
+    #[cfg(feature = "tracing")]
+    trace::exited(&tracing::Span::current(), &status, started);
+
+    result?;
+
+    let stdout_buffer = stdout_buffer.into_inner();
+    let (stdout, chunks) = stdout_buffer
+        .unwrap_or_else(PoisonError::into_inner)
+        .into_captured();
+    let (stderr, _) = stderr_buffer
+        .into_inner()
+        .unwrap_or_else(PoisonError::into_inner)
+        .into_captured();
+
+    Ok(CaptureOutput {
+        status,
+        stdout,
+        stderr,
+        chunks,
+        usage,
+    })
+}
+
+/// Spawns the given command then collects its output, like
+/// [`std::process::Command::output`], retaining at most the given
+/// limits of bytes of stdout and stderr.
+///
+/// Streams are read concurrently, stderr from a scoped thread. The
+/// child process is killed as soon as a stream exceeds the limit with
+/// the [`OverflowPolicy::Error`] policy, and is always waited for.
+#[cfg(feature = "std")]
+pub(crate) fn spawn_then_wait_with_output(
+    mut command: StdCommand,
+    (stdout_limit, stderr_limit): (Option<OutputLimit>, Option<OutputLimit>),
+    group: Option<i32>,
+) -> io::Result<Output> {
+    let mut child = killable(command.spawn()?, group);
+
+    let started = Instant::now();
+    #[cfg(feature = "tracing")]
+    trace::spawned(&tracing::Span::current(), Some(child.id()));
+
+    drop(child.process.stdin.take());
+    let stdout = child.process.stdout.take();
+    let stderr = child.process.stderr.take();
+    let child = Mutex::new(child);
+
+    let stdout_buffer = Mutex::new(CaptureBuffer::new(stdout_limit));
+    let stderr_buffer = Mutex::new(CaptureBuffer::new(stderr_limit));
+    let result = read_streams(stdout, stderr, &stdout_buffer, &stderr_buffer, None, &child);
+
+    let mut child = child.into_inner().unwrap_or_else(PoisonError::into_inner);
+    let (status, usage) = wait(&mut child.process, started)?;
+
+    #[cfg(feature = "tracing")]
+    trace::exited(&tracing::Span::current(), &status, started);
+
+    result?;
+
+    let captured = |buffer: Mutex<CaptureBuffer>| {
+        let buffer = buffer.into_inner().unwrap_or_else(PoisonError::into_inner);
+        buffer.into_captured().0
+    };
+
+    let stdout = captured(stdout_buffer);
+    let stderr = captured(stderr_buffer);
+
+    Ok(Output {
+        status,
+        stdout: stdout.bytes,
+        stderr: stderr.bytes,
+        stdout_discarded: stdout.discarded,
+        stderr_discarded: stderr.discarded,
+        usage,
+    })
+}
+
+/// Wraps the given spawned process into a [`Child`] handle, so that
+/// it can be killed along with the process group it leads, if any,
+/// like dropped handles are.
+fn killable(process: StdChild, group: Option<i32>) -> Child {
+    let child = Child::new(process);
+
+    #[cfg(unix)]
+    let child = child.with_group(group);
+    #[cfg(not(unix))]
+    let _ = group;
+
+    #[cfg(target_os = "linux")]
+    let pidfd = super::unix::pidfd_open(child.id());
+    #[cfg(target_os = "linux")]
+    let child = child.with_pidfd(pidfd);
+
+    child
+}
+
+/// Reads the given streams concurrently until the end of file,
+/// stderr from a scoped thread, into the given buffers and the given
+/// tee, if any.
+fn read_streams(
+    stdout: Option<impl Read + Send>,
+    stderr: Option<impl Read + Send>,
+    stdout_buffer: &Mutex<CaptureBuffer>,
+    stderr_buffer: &Mutex<CaptureBuffer>,
+    tee: Option<&Tee>,
+    child: &Mutex<Child>,
+) -> io::Result<()> {
+    thread::scope(|scope| {
+        let stderr = scope.spawn(|| {
+            let stream = Stream::Stderr;
+            read_capped(stderr, stream, stderr_buffer, tee, child)
+        });
+
+        let stdout = read_capped(stdout, Stream::Stdout, stdout_buffer, tee, child);
+
+        let stderr = stderr
+            .join()
+            .unwrap_or_else(|_| Err(io::Error::other("cannot read stderr")));
+
+        stdout.and(stderr)
+    })
+}
+
+/// Reads the given stream until the end of file, into the given
+/// buffer and the given tee, if any.
+fn read_capped(
+    reader: Option<impl Read>,
+    stream: Stream,
+    buffer: &Mutex<CaptureBuffer>,
+    tee: Option<&Tee>,
+    child: &Mutex<Child>,
+) -> io::Result<()> {
+    let Some(mut reader) = reader else {
+        return Ok(());
+    };
+
+    let mut chunk = [0; 8192];
+
+    let result = loop {
+        let n = match reader.read(&mut chunk) {
+            Ok(0) => break Ok(()),
+            Ok(n) => n,
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+            Err(err) => break Err(err),
+        };
+
+        if let Some(tee) = tee {
+            if let Err(err) = forward(tee, stream, &chunk[..n]) {
+                break Err(err);
+            }
+        }
+
+        let mut buffer = buffer.lock().unwrap_or_else(PoisonError::into_inner);
+
+        if let Err(max) = buffer.push(stream, &chunk[..n]) {
+            break Err(CaptureError::LimitExceeded(stream, max).into());
+        }
+    };
+
+    // the other stream and the wait would block until the child
+    // process exits by itself, which may never happen
+    if result.is_err() {
+        let child = child.lock().unwrap_or_else(PoisonError::into_inner);
+        // errors mean that the child process already exited
+        #[cfg(unix)]
+        let _ = super::unix::kill(&child, Signal::KILL);
+        #[cfg(not(unix))]
+        let _ = child.process.kill();
+    }
+
+    result
+}
+
+/// Forwards the given bytes read from the given stream to the given
+/// tee.
+fn forward(tee: &Tee, stream: Stream, bytes: &[u8]) -> io::Result<()> {
+    match tee {
+        Tee::Inherit if stream == Stream::Stdout => {
+            let mut stdout = io::stdout().lock();
+            stdout.write_all(bytes)?;
+            stdout.flush()
+        }
+        Tee::Inherit => io::stderr().lock().write_all(bytes),
+        Tee::Sink(sink) => {
+            let mut sink = sink.lock().unwrap_or_else(PoisonError::into_inner);
+            sink.write_all(bytes)?;
+            sink.flush()
+        }
+    }
+}
+
+/// The buffer retaining the bytes of captured streams.
+#[derive(Debug)]
+pub(crate) struct CaptureBuffer {
+    bytes: VecDeque<u8>,
+    chunks: Option<VecDeque<Chunk>>,
+    limit: Option<OutputLimit>,
+    discarded: u64,
+}
+
+impl CaptureBuffer {
+    pub(crate) fn new(limit: Option<OutputLimit>) -> Self {
+        Self {
+            bytes: VecDeque::new(),
+            chunks: None,
+            limit,
+            discarded: 0,
+        }
+    }
+
+    /// Creates a buffer also retaining chunks, with the stream they
+    /// come from.
+    fn tagged(limit: Option<OutputLimit>) -> Self {
+        Self {
+            chunks: Some(VecDeque::new()),
+            ..Self::new(limit)
+        }
+    }
+
+    /// Pushes the given bytes, applying the overflow policy.
+    ///
+    /// Returns the maximum number of bytes as error if the buffer
+    /// exceeded its limit with the [`OverflowPolicy::Error`] policy.
+    pub(crate) fn push(&mut self, stream: Stream, bytes: &[u8]) -> Result<(), usize> {
+        let (kept, dropped) = match self.limit {
+            None => (bytes, 0),
+            Some(OutputLimit { max_bytes, policy }) => match policy {
+                OverflowPolicy::Error if self.bytes.len() + bytes.len() > max_bytes => {
+                    return Err(max_bytes);
+                }
+                OverflowPolicy::Error => (bytes, 0),
+                OverflowPolicy::KeepHead => {
+                    let n = bytes.len().min(max_bytes - self.bytes.len());
+                    self.discarded += (bytes.len() - n) as u64;
+                    (&bytes[..n], 0)
+                }
+                OverflowPolicy::KeepTail => {
+                    let len = self.bytes.len() + bytes.len();
+                    (bytes, len.saturating_sub(max_bytes))
+                }
+            },
+        };
+
+        self.bytes.extend(kept);
+        self.bytes.drain(..dropped);
+        self.discarded += dropped as u64;
+
+        if let Some(chunks) = self.chunks.as_mut() {
+            if !kept.is_empty() {
+                let bytes = kept.to_vec();
+                chunks.push_back(Chunk { stream, bytes });
+            }
+
+            let mut dropped = dropped;
+
+            while let Some(chunk) = chunks.front_mut().filter(|_| dropped > 0) {
+                if chunk.bytes.len() <= dropped {
+                    dropped -= chunk.bytes.len();
+                    chunks.pop_front();
+                } else {
+                    chunk.bytes.drain(..dropped);
+                    dropped = 0;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    pub(crate) fn into_captured(self) -> (Captured, Vec<Chunk>) {
+        let captured = Captured {
+            bytes: self.bytes.into(),
+            discarded: self.discarded,
+        };
+
+        let chunks = self.chunks.map(Into::into).unwrap_or_default();
+
+        (captured, chunks)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Chunk, OutputLimit, Stream};
+
+    use super::CaptureBuffer;
+
+    #[test]
+    fn capture_buffer() {
+        let mut buffer = CaptureBuffer::new(None);
+        buffer.push(Stream::Stdout, b"abc").unwrap();
+        buffer.push(Stream::Stdout, b"def").unwrap();
+        let (captured, _) = buffer.into_captured();
+        assert_eq!(b"abcdef", captured.bytes.as_slice());
+        assert!(!captured.is_truncated());
+
+        let mut buffer = CaptureBuffer::new(Some(OutputLimit::error(4)));
+        buffer.push(Stream::Stdout, b"abc").unwrap();
+        assert_eq!(Err(4), buffer.push(Stream::Stdout, b"def"));
+
+        let mut buffer = CaptureBuffer::new(Some(OutputLimit::keep_head(4)));
+        buffer.push(Stream::Stdout, b"abc").unwrap();
+        buffer.push(Stream::Stdout, b"def").unwrap();
+        let (captured, _) = buffer.into_captured();
+        assert_eq!(b"abcd", captured.bytes.as_slice());
+        assert_eq!(2, captured.discarded);
+
+        let mut buffer = CaptureBuffer::new(Some(OutputLimit::keep_tail(4)));
+        buffer.push(Stream::Stdout, b"abc").unwrap();
+        buffer.push(Stream::Stdout, b"def").unwrap();
+        buffer.push(Stream::Stdout, b"g").unwrap();
+        let (captured, _) = buffer.into_captured();
+        assert_eq!(b"defg", captured.bytes.as_slice());
+        assert_eq!(3, captured.discarded);
+    }
+
+    #[test]
+    fn capture_buffer_tagged() {
+        let chunk = |stream, bytes: &[u8]| Chunk {
+            stream,
+            bytes: bytes.to_vec(),
+        };
+
+        let mut buffer = CaptureBuffer::tagged(Some(OutputLimit::keep_tail(5)));
+        buffer.push(Stream::Stdout, b"abc").unwrap();
+        buffer.push(Stream::Stderr, b"de").unwrap();
+        buffer.push(Stream::Stdout, b"fg").unwrap();
+        let (captured, chunks) = buffer.into_captured();
+        assert_eq!(b"cdefg", captured.bytes.as_slice());
+        assert_eq!(
+            vec![
+                chunk(Stream::Stdout, b"c"),
+                chunk(Stream::Stderr, b"de"),
+                chunk(Stream::Stdout, b"fg"),
+            ],
+            chunks
+        );
+    }
+}
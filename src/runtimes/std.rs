@@ -2,10 +2,23 @@
 
 use std::{
     io,
-    process::{Command as StdCommand, Output},
+    process::{Command as StdCommand, Stdio},
+    thread,
+    time::{Duration, Instant},
 };
 
-use crate::{Command, Io, SpawnOutput};
+#[cfg(unix)]
+use std::{fs::FileType, path::PathBuf};
+
+use crate::{Capture, CaptureOutput, Child, Command, Io, Output, OutputLimit, SpawnOutput};
+#[cfg(unix)]
+use crate::{Job, ReadOutput, Signal};
+#[cfg(target_os = "linux")]
+use crate::{Pty, WindowSize};
+
+use super::shared;
+#[cfg(feature = "tracing")]
+use super::trace;
 
 /// The main runtime I/O handler.
 ///
@@ -24,6 +37,30 @@ pub fn handle(io: Io) -> io::Result<Io> {
 
         Io::SpawnThenWait(io) => spawn_then_wait(io),
         Io::SpawnThenWaitWithOutput(io) => spawn_then_wait_with_output(io),
+        Io::SpawnThenCapture(io) => spawn_then_capture(io),
+        Io::Sleep(io) => sleep(io),
+        Io::Spawn(io) => spawn(io),
+        Io::Wait(io) => wait(io),
+        #[cfg(unix)]
+        Io::SpawnDetached(io) => spawn_detached(io),
+        #[cfg(unix)]
+        Io::Kill(io) => kill(io),
+        #[cfg(unix)]
+        Io::WaitTimeout(io) => wait_timeout(io),
+        #[cfg(unix)]
+        Io::Read(io) => read(io),
+        #[cfg(unix)]
+        Io::Write(io) => write(io),
+        #[cfg(unix)]
+        Io::WaitAny(io) => wait_any(io),
+        #[cfg(unix)]
+        Io::Stat(io) => stat(io),
+        #[cfg(unix)]
+        Io::ArgMax(io) => arg_max(io),
+        #[cfg(target_os = "linux")]
+        Io::SpawnPty(io) => spawn_pty(io),
+        #[cfg(target_os = "linux")]
+        Io::ResizePty(io) => resize_pty(io),
     }
 }
 
@@ -33,23 +70,39 @@ pub fn handle(io: Io) -> io::Result<Io> {
 /// command builder, spawns a process, collects std{in,out,err} then
 /// waits for the exit status.
 pub fn spawn_then_wait(input: Result<SpawnOutput, Command>) -> io::Result<Io> {
-    let Err(command) = input else {
+    let Err(mut command) = input else {
         let kind = io::ErrorKind::InvalidInput;
         return Err(io::Error::new(kind, "missing command"));
     };
 
+    shared::check_current_dir(&command)?;
+    shared::open_redirects(&mut command)?;
+    let map_err = shared::map_spawn_error(&command);
+
+    #[cfg(feature = "tracing")]
+    let span = trace::span(&command);
+
     let mut command = StdCommand::from(command);
-    let mut child = command.spawn()?;
+    let started = Instant::now();
+    let mut process = command.spawn().map_err(map_err)?;
 
-    let stdin = child.stdin.take();
-    let stdout = child.stdout.take();
-    let stderr = child.stderr.take();
+    #[cfg(feature = "tracing")]
+    trace::spawned(&span, Some(process.id()));
+
+    let stdin = process.stdin.take();
+    let stdout = process.stdout.take();
+    let stderr = process.stderr.take();
+    let (status, usage) = shared::wait(&mut process, started)?;
+
+    #[cfg(feature = "tracing")]
+    trace::exited(&span, &status, started);
 
     let output = SpawnOutput {
-        status: child.wait()?,
+        status,
         stdin: stdin.map(Into::into),
         stdout: stdout.map(Into::into),
         stderr: stderr.map(Into::into),
+        usage,
     };
 
     Ok(Io::SpawnThenWait(Ok(output)))
@@ -58,23 +111,348 @@ pub fn spawn_then_wait(input: Result<SpawnOutput, Command>) -> io::Result<Io> {
 /// Spawns a process then wait for its child's output.
 ///
 /// This function builds a [`std::process::Command`] from the flow's
-/// command builder, spawns a process, then waits for the output.
-pub fn spawn_then_wait_with_output(input: Result<Output, Command>) -> io::Result<Io> {
-    let Err(command) = input else {
+/// command builder, spawns a process, then reads its stdout and
+/// stderr within their optional limit before waiting for it.
+pub fn spawn_then_wait_with_output(
+    input: Result<Output, (Command, Option<OutputLimit>, Option<OutputLimit>)>,
+) -> io::Result<Io> {
+    let Err((mut command, stdout_limit, stderr_limit)) = input else {
         let kind = io::ErrorKind::InvalidInput;
         return Err(io::Error::new(kind, "missing command"));
     };
 
-    let mut command = StdCommand::from(command);
-    let output = command.output()?;
+    shared::check_current_dir(&command)?;
+    shared::open_redirects(&mut command)?;
+    shared::default_output_stdio(&mut command);
+    let map_err = shared::map_spawn_error(&command);
+
+    #[cfg(unix)]
+    let group = command.get_process_group();
+    #[cfg(not(unix))]
+    let group = None;
+
+    #[cfg(feature = "tracing")]
+    let _span = trace::span(&command).entered();
+
+    let command = StdCommand::from(command);
+    let limits = (stdout_limit, stderr_limit);
+    let output = shared::spawn_then_wait_with_output(command, limits, group).map_err(map_err)?;
 
     Ok(Io::SpawnThenWaitWithOutput(Ok(output)))
 }
 
+/// Spawns a process then captures its output.
+///
+/// This function builds a [`std::process::Command`] from the flow's
+/// command builder, spawns a process, then reads its stdout and
+/// stderr within the limits of the capture configuration.
+pub fn spawn_then_capture(input: Result<CaptureOutput, (Command, Capture)>) -> io::Result<Io> {
+    let Err((mut command, capture)) = input else {
+        let kind = io::ErrorKind::InvalidInput;
+        return Err(io::Error::new(kind, "missing command"));
+    };
+
+    shared::check_current_dir(&command)?;
+    shared::open_redirects(&mut command)?;
+    let map_err = shared::map_spawn_error(&command);
+
+    if command.stdin.is_none() {
+        command.stdin(Stdio::null());
+    }
+
+    #[cfg(unix)]
+    let group = command.get_process_group();
+    #[cfg(not(unix))]
+    let group = None;
+
+    #[cfg(feature = "tracing")]
+    let _span = trace::span(&command).entered();
+
+    let command = StdCommand::from(command);
+    let output = shared::spawn_then_capture(command, capture, group).map_err(map_err)?;
+
+    Ok(Io::SpawnThenCapture(Ok(output)))
+}
+
+/// Sleeps for the requested duration.
+pub fn sleep(input: Result<(), Duration>) -> io::Result<Io> {
+    let Err(duration) = input else {
+        let kind = io::ErrorKind::InvalidInput;
+        return Err(io::Error::new(kind, "missing duration"));
+    };
+
+    thread::sleep(duration);
+
+    Ok(Io::Sleep(Ok(())))
+}
+
+/// Spawns a process without waiting for it.
+///
+/// This function builds a [`std::process::Command`] from the flow's
+/// command builder, spawns a process then returns its [`Child`]
+/// handle.
+pub fn spawn(input: Result<Child, Command>) -> io::Result<Io> {
+    let Err(mut command) = input else {
+        let kind = io::ErrorKind::InvalidInput;
+        return Err(io::Error::new(kind, "missing command"));
+    };
+
+    shared::check_current_dir(&command)?;
+    shared::open_redirects(&mut command)?;
+    let map_err = shared::map_spawn_error(&command);
+
+    let kill_on_drop = command.kill_on_drop;
+
+    #[cfg(unix)]
+    let group = command.get_process_group();
+
+    #[cfg(feature = "tracing")]
+    let span = trace::span(&command);
+
+    let mut command = StdCommand::from(command);
+    let child = Child::new(command.spawn().map_err(map_err)?).with_kill_on_drop(kill_on_drop);
+
+    #[cfg(feature = "tracing")]
+    let child = trace::attach(child, span);
+
+    #[cfg(unix)]
+    let child = child.with_group(group);
+
+    #[cfg(target_os = "linux")]
+    let pidfd = super::unix::pidfd_open(child.id());
+    #[cfg(target_os = "linux")]
+    let child = child.with_pidfd(pidfd);
+
+    Ok(Io::Spawn(Ok(child)))
+}
+
+/// Spawns a detached process, which outlives its parent.
+///
+/// This function builds a [`std::process::Command`] from the flow's
+/// command builder, redirects std{in,out,err} not configured to
+/// `/dev/null`, then spawns a process in a new session. The process
+/// forks again then exits, and the ID of the grandchild is
+/// returned.
+#[cfg(unix)]
+pub fn spawn_detached(input: Result<u32, Command>) -> io::Result<Io> {
+    let Err(mut command) = input else {
+        let kind = io::ErrorKind::InvalidInput;
+        return Err(io::Error::new(kind, "missing command"));
+    };
+
+    shared::check_current_dir(&command)?;
+    shared::open_redirects(&mut command)?;
+    shared::default_null_stdio(&mut command);
+    let map_err = shared::map_spawn_error(&command);
+
+    // the process already runs in a new session
+    command.setsid = false;
+    command.process_group = None;
+
+    #[cfg(feature = "tracing")]
+    let span = trace::span(&command);
+
+    let command = StdCommand::from(command);
+    let pid = super::unix::spawn_detached(command).map_err(map_err)?;
+
+    #[cfg(feature = "tracing")]
+    trace::spawned(&span, Some(pid));
+
+    Ok(Io::SpawnDetached(Ok(pid)))
+}
+
+/// Waits for a spawned child process' exit status.
+///
+/// This function collects std{in,out,err} of the given child process
+/// then waits for the exit status.
+pub fn wait(input: Result<SpawnOutput, Child>) -> io::Result<Io> {
+    let Err(child) = input else {
+        let kind = io::ErrorKind::InvalidInput;
+        return Err(io::Error::new(kind, "missing child"));
+    };
+
+    let output = shared::wait_child(child)?;
+
+    Ok(Io::Wait(Ok(output)))
+}
+
+/// Waits for a spawned child process' exit status, for at most the
+/// given duration.
+///
+/// The child process is given back if it is still running once the
+/// timeout expires.
+#[cfg(unix)]
+pub fn wait_timeout(
+    input: Result<Result<SpawnOutput, Child>, (Child, Duration)>,
+) -> io::Result<Io> {
+    let Err((child, timeout)) = input else {
+        let kind = io::ErrorKind::InvalidInput;
+        return Err(io::Error::new(kind, "missing child"));
+    };
+
+    if !super::unix::wait_timeout(&child, timeout)? {
+        return Ok(Io::WaitTimeout(Ok(Err(child))));
+    }
+
+    let output = shared::wait_child(child)?;
+
+    Ok(Io::WaitTimeout(Ok(Ok(output))))
+}
+
+/// Queries the type of the file at the given path.
+#[cfg(unix)]
+pub fn stat(input: Result<Option<FileType>, PathBuf>) -> io::Result<Io> {
+    let Err(path) = input else {
+        let kind = io::ErrorKind::InvalidInput;
+        return Err(io::Error::new(kind, "missing path"));
+    };
+
+    let file_type = shared::stat(&path)?;
+
+    Ok(Io::Stat(Ok(file_type)))
+}
+
+/// Sends a signal to a spawned child process.
+///
+/// The signal is sent to the whole process group if the child
+/// process leads one.
+#[cfg(unix)]
+pub fn kill(input: Result<Child, (Child, Signal)>) -> io::Result<Io> {
+    let Err((child, signal)) = input else {
+        let kind = io::ErrorKind::InvalidInput;
+        return Err(io::Error::new(kind, "missing child"));
+    };
+
+    super::unix::kill(&child, signal)?;
+
+    #[cfg(feature = "tracing")]
+    trace::killed(&child.span, signal);
+
+    Ok(Io::Kill(Ok(child)))
+}
+
+/// Reads bytes from a spawned child process' output streams.
+///
+/// This function waits for bytes on either the piped stdout or stderr
+/// of the given child process, until the optional timeout.
+#[cfg(unix)]
+pub fn read(input: Result<(Child, ReadOutput), (Child, Option<Duration>)>) -> io::Result<Io> {
+    let Err((mut child, timeout)) = input else {
+        let kind = io::ErrorKind::InvalidInput;
+        return Err(io::Error::new(kind, "missing child"));
+    };
+
+    let output = super::unix::read(&mut child, timeout)?;
+
+    #[cfg(feature = "tracing")]
+    if output == ReadOutput::Timeout {
+        trace::timed_out(&child.span, timeout);
+    }
+
+    Ok(Io::Read(Ok((child, output))))
+}
+
+/// Writes bytes to a spawned child process' stdin.
+#[cfg(unix)]
+pub fn write(input: Result<Child, (Child, Vec<u8>)>) -> io::Result<Io> {
+    let Err((mut child, bytes)) = input else {
+        let kind = io::ErrorKind::InvalidInput;
+        return Err(io::Error::new(kind, "missing child"));
+    };
+
+    super::unix::write(&mut child, &bytes)?;
+
+    Ok(Io::Write(Ok(child)))
+}
+
+/// Waits for any of the given running jobs to complete.
+///
+/// This function collects the output of all the given jobs until one
+/// of them completes. On error, remaining jobs are killed.
+#[cfg(unix)]
+pub fn wait_any(input: Result<(Vec<Job>, usize, Output), Vec<Job>>) -> io::Result<Io> {
+    let Err(mut jobs) = input else {
+        let kind = io::ErrorKind::InvalidInput;
+        return Err(io::Error::new(kind, "missing jobs"));
+    };
+
+    match super::unix::wait_any(&mut jobs).and_then(shared::wait_job) {
+        Ok((index, output)) => Ok(Io::WaitAny(Ok((jobs, index, output)))),
+        Err(err) => {
+            super::unix::abort_jobs(jobs);
+            Err(err)
+        }
+    }
+}
+
+/// Computes the space available for the arguments of a new process.
+#[cfg(unix)]
+pub fn arg_max(input: Result<usize, ()>) -> io::Result<Io> {
+    if input.is_ok() {
+        let kind = io::ErrorKind::InvalidInput;
+        return Err(io::Error::new(kind, "argument size limit already computed"));
+    }
+
+    Ok(Io::ArgMax(Ok(super::unix::arg_max())))
+}
+
+/// Spawns a process in a new pseudo-terminal.
+///
+/// This function allocates a pseudo-terminal pair, builds a
+/// [`std::process::Command`] from the flow's command builder
+/// then spawns a process in a new session whose controlling terminal
+/// is the slave side.
+#[cfg(target_os = "linux")]
+pub fn spawn_pty(input: Result<Pty, (Command, WindowSize)>) -> io::Result<Io> {
+    let Err((mut command, size)) = input else {
+        let kind = io::ErrorKind::InvalidInput;
+        return Err(io::Error::new(kind, "missing command"));
+    };
+
+    shared::check_current_dir(&command)?;
+    let map_err = shared::map_spawn_error(&command);
+
+    // the child process already leads a new session
+    command.setsid = false;
+    command.process_group = None;
+
+    #[cfg(feature = "tracing")]
+    let span = trace::span(&command);
+
+    let kill_on_drop = command.kill_on_drop;
+    let command = StdCommand::from(command);
+    let mut pty = super::unix::spawn_pty(command, size, map_err)?;
+    pty.child.kill_on_drop = kill_on_drop;
+
+    #[cfg(feature = "tracing")]
+    let pty = Pty {
+        child: trace::attach(pty.child, span),
+        ..pty
+    };
+
+    Ok(Io::SpawnPty(Ok(pty)))
+}
+
+/// Resizes the window of a pseudo-terminal.
+#[cfg(target_os = "linux")]
+pub fn resize_pty(input: Result<Pty, (Pty, WindowSize)>) -> io::Result<Io> {
+    let Err((pty, size)) = input else {
+        let kind = io::ErrorKind::InvalidInput;
+        return Err(io::Error::new(kind, "missing pty"));
+    };
+
+    super::unix::set_window_size(&pty.master, size)?;
+
+    Ok(Io::ResizePty(Ok(pty)))
+}
+
 /// Converts a [`Command`] builder to a [`std::process::Command`].
 impl From<Command> for StdCommand {
-    fn from(builder: Command) -> Self {
-        let mut command = StdCommand::new(builder.program);
+    fn from(mut builder: Command) -> Self {
+        let mut command = StdCommand::new(&builder.program);
+
+        #[cfg(unix)]
+        super::unix::configure(&mut command, &mut builder);
 
         if let Some(args) = builder.args {
             for arg in args {
@@ -82,6 +460,11 @@ impl From<Command> for StdCommand {
             }
         }
 
+        if let Some(vars) = shared::inherited_envs(&builder.env_inherit) {
+            command.env_clear();
+            command.envs(vars);
+        }
+
         if let Some(envs) = builder.envs {
             for (key, val) in envs {
                 command.env(key, val);
@@ -107,3 +490,1135 @@ impl From<Command> for StdCommand {
         command
     }
 }
+
+#[cfg(all(test, unix))]
+mod tests {
+    use std::{
+        io::{pipe, BufRead, BufReader, Read, Write},
+        os::unix::process::ExitStatusExt,
+        process::Stdio,
+        sync::{Arc, Mutex},
+        time::{Duration, Instant},
+    };
+
+    use crate::{
+        coroutines::{
+            Expect, ExpectOutput, ExpectStep, JobPool, Kill, Probe, RestartPolicy, Retry,
+            RetryPolicy, Spawn, SpawnDetached, SpawnThenCapture, SpawnThenWait,
+            SpawnThenWaitWithOutput, Supervise, SuperviseEvent, Terminate, TerminateOutput,
+            Termination, TerminationPolicy, Wait, WaitReady, WaitReadyOutput, Xargs,
+        },
+        Capture, CaptureError, CaptureMode, Child, Chunk, Command, EnvInherit, OutputLimit,
+        RlimitResource, Signal, SpawnError, Stream, Tee,
+    };
+
+    use super::handle;
+
+    /// Drives the given coroutine resume function to completion,
+    /// handling each requested I/O with the std runtime.
+    fn run<T>(
+        mut resume: impl FnMut(Option<crate::Io>) -> Result<T, crate::Io>,
+    ) -> std::io::Result<T> {
+        let mut arg = None;
+
+        loop {
+            match resume(arg.take()) {
+                Ok(output) => break Ok(output),
+                Err(io) => arg = Some(handle(io)?),
+            }
+        }
+    }
+
+    #[test]
+    fn kill_process_group() {
+        let mut command = Command::new("sh");
+        command.arg("-c").arg("sleep 60 & echo ready; wait");
+        command.stdout(Stdio::piped());
+        command.process_group(0);
+
+        let mut spawn = Spawn::new(command);
+        let mut child = run(|io| spawn.resume(io)).unwrap();
+
+        assert_eq!(Some(child.id()), child.group);
+
+        let mut stdout = BufReader::new(child.process.stdout.take().unwrap());
+        let mut line = String::new();
+        stdout.read_line(&mut line).unwrap();
+        assert_eq!("ready\n", line);
+
+        let mut kill = Kill::new(child, Signal::TERM);
+        let child = run(|io| kill.resume(io)).unwrap();
+
+        let mut wait = Wait::new(child);
+        let output = run(|io| wait.resume(io)).unwrap();
+
+        assert!(!output.status.success());
+
+        // the grandchild holds the pipe open as long as it runs
+        let mut rest = String::new();
+        stdout.read_to_string(&mut rest).unwrap();
+        assert_eq!("", rest);
+    }
+
+    #[test]
+    fn join_process_group() {
+        let spawn = |command| {
+            let mut spawn = Spawn::new(command);
+            run(|io| spawn.resume(io)).unwrap()
+        };
+
+        let mut command = Command::new("sleep");
+        command.arg("60").kill_on_drop(true).process_group(0);
+        let leader = spawn(command);
+
+        let mut command = Command::new("sleep");
+        command.arg("60").kill_on_drop(true);
+        command.process_group(leader.id() as i32);
+        let member = spawn(command);
+
+        // the member joined the group but does not lead it
+        assert_eq!(Some(leader.id()), leader.group);
+        assert_eq!(None, member.group);
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn kill_pidfd() {
+        let mut command = Command::new("sleep");
+        command.arg("60");
+
+        let mut spawn = Spawn::new(command);
+        let child = run(|io| spawn.resume(io)).unwrap();
+
+        // kernels older than 5.3 do not support process file
+        // descriptors, in which case runtimes fall back to the ID
+        if child.pidfd.is_none() {
+            return;
+        }
+
+        let mut kill = Kill::new(child, Signal::TERM);
+        let child = run(|io| kill.resume(io)).unwrap();
+
+        let mut wait = Wait::new(child);
+        let output = run(|io| wait.resume(io)).unwrap();
+
+        assert_eq!(Some(libc::SIGTERM), output.status.signal());
+    }
+
+    #[test]
+    fn kill_on_drop() {
+        let mut command = Command::new("sleep");
+        command.arg("60").kill_on_drop(true);
+
+        let mut spawn = Spawn::new(command);
+        let child = run(|io| spawn.resume(io)).unwrap();
+
+        let pid = child.id() as libc::pid_t;
+        drop(child);
+
+        // the child process is killed and reaped in the background
+        for _ in 0..100 {
+            if unsafe { libc::kill(pid, 0) } == -1 {
+                break;
+            }
+
+            std::thread::sleep(Duration::from_millis(10));
+        }
+
+        let err = std::io::Error::last_os_error;
+        assert_eq!(-1, unsafe { libc::kill(pid, 0) });
+        assert_eq!(Some(libc::ESRCH), err().raw_os_error());
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn parent_death_signal() {
+        let mut command = Command::new("sleep");
+        command.arg("60").parent_death_signal(Signal::KILL);
+
+        // the signal is sent as soon as the spawning thread exits
+        let child = std::thread::spawn(move || {
+            let mut spawn = Spawn::new(command);
+            run(|io| spawn.resume(io)).unwrap()
+        });
+        let child = child.join().unwrap();
+
+        let mut wait = Wait::new(child);
+        let output = run(|io| wait.resume(io)).unwrap();
+
+        assert_eq!(Some(libc::SIGKILL), output.status.signal());
+    }
+
+    #[test]
+    fn rlimit_cpu() {
+        let mut command = Command::new("sh");
+        command.arg("-c").arg("while :; do :; done");
+        command.rlimit(RlimitResource::Cpu, Some(1), Some(2));
+
+        let mut spawn = SpawnThenWait::new(command);
+        let output = run(|io| spawn.resume(io)).unwrap();
+
+        let signal = output.status.signal();
+        assert!(matches!(signal, Some(libc::SIGXCPU | libc::SIGKILL)));
+    }
+
+    #[test]
+    fn rlimit_open_files() {
+        let mut command = Command::new("sh");
+        command
+            .arg("-c")
+            .arg("exec 3</dev/null 4</dev/null 5</dev/null");
+        command.stderr(Stdio::null());
+        command.rlimit(RlimitResource::OpenFiles, Some(4), Some(4));
+
+        let mut spawn = SpawnThenWait::new(command);
+        let output = run(|io| spawn.resume(io)).unwrap();
+
+        assert!(!output.status.success());
+    }
+
+    #[test]
+    fn rlimit_soft_only() {
+        let mut command = Command::new("sh");
+        command.arg("-c").arg("ulimit -Sn; ulimit -Hn");
+        command.stdout(Stdio::piped());
+        command.rlimit(RlimitResource::OpenFiles, Some(64), None);
+
+        let mut spawn = SpawnThenWaitWithOutput::new(command);
+        let output = run(|io| spawn.resume(io)).unwrap();
+
+        let mut limit = libc::rlimit {
+            rlim_cur: 0,
+            rlim_max: 0,
+        };
+        assert_eq!(0, unsafe {
+            libc::getrlimit(libc::RLIMIT_NOFILE, &mut limit)
+        });
+
+        // the hard limit is inherited from the current process
+        let hard = match limit.rlim_max {
+            libc::RLIM_INFINITY => String::from("unlimited"),
+            hard => hard.to_string(),
+        };
+
+        assert!(output.status.success());
+        let stdout = String::from_utf8(output.stdout).unwrap();
+        assert_eq!(format!("64\n{hard}\n"), stdout);
+    }
+
+    #[test]
+    fn inherit_fds() {
+        let (hello, mut writer) = pipe().unwrap();
+        writer.write_all(b"hello").unwrap();
+        drop(writer);
+
+        let (world, mut writer) = pipe().unwrap();
+        writer.write_all(b" world").unwrap();
+        drop(writer);
+
+        let mut command = Command::new("sh");
+        command.arg("-c").arg("cat <&3; cat <&4");
+        command.inherit_fd(3, hello).inherit_fd(4, world);
+
+        let mut spawn = SpawnThenWaitWithOutput::new(command);
+        let output = run(|io| spawn.resume(io)).unwrap();
+
+        assert!(output.status.success());
+        assert_eq!(b"hello world", output.stdout.as_slice());
+    }
+
+    #[test]
+    fn inherit_fds_exec_error() {
+        use std::{fs::File, os::fd::AsRawFd};
+
+        let sources: Vec<_> = (0..4).map(|_| File::open("/dev/null").unwrap()).collect();
+
+        // the lowest free descriptor, where the standard library
+        // would open the pipe reporting exec errors
+        let fd = File::open("/dev/null").unwrap().as_raw_fd();
+
+        let mut command = Command::new("/missing");
+        command.current_dir("/");
+
+        for (child_fd, source) in (fd..).zip(sources) {
+            command.inherit_fd(child_fd, source);
+        }
+
+        let err = spawn_error(command);
+        assert!(matches!(err, SpawnError::ProgramNotFound(p) if p == "/missing"));
+    }
+
+    #[test]
+    fn credentials_current_dir() {
+        // switching user requires privileges
+        if unsafe { libc::geteuid() } != 0 {
+            return;
+        }
+
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempdir::TempDir::new("credentials").unwrap();
+        let private = dir.path().join("private");
+        std::fs::create_dir(&private).unwrap();
+        std::fs::set_permissions(dir.path(), PermissionsExt::from_mode(0o700)).unwrap();
+
+        // the directory is entered once privileges dropped
+        let mut command = Command::new("true");
+        command.uid(65534).gid(65534).current_dir(&private);
+
+        let err = spawn_error_io(command);
+        assert_eq!(std::io::ErrorKind::PermissionDenied, err.kind());
+
+        let mut command = Command::new("pwd");
+        command.uid(65534).gid(65534).current_dir("/tmp");
+        command.stdout(Stdio::piped());
+
+        let mut spawn = SpawnThenWaitWithOutput::new(command);
+        let output = run(|io| spawn.resume(io)).unwrap();
+
+        assert!(output.status.success());
+        assert_eq!(b"/tmp\n", output.stdout.as_slice());
+    }
+
+    fn spawn_error(command: Command) -> SpawnError {
+        let err = spawn_error_io(command);
+        *err.into_inner().unwrap().downcast().unwrap()
+    }
+
+    fn spawn_error_io(command: Command) -> std::io::Error {
+        let mut spawn = SpawnThenWait::new(command);
+        let io = spawn.resume(None).unwrap_err();
+        handle(io).unwrap_err()
+    }
+
+    #[test]
+    fn current_dir_not_found() {
+        let mut command = Command::new("true");
+        command.current_dir("/missing");
+
+        let err = spawn_error(command);
+        assert!(matches!(err, SpawnError::CurrentDirNotFound(p) if p.to_str() == Some("/missing")));
+    }
+
+    #[test]
+    fn current_dir_not_a_directory() {
+        let mut command = Command::new("true");
+        command.current_dir("/dev/null");
+
+        let err = spawn_error(command);
+        assert!(
+            matches!(err, SpawnError::CurrentDirNotADirectory(p) if p.to_str() == Some("/dev/null"))
+        );
+    }
+
+    #[test]
+    fn redirect_not_found() {
+        let mut command = Command::new("true");
+        command.stdin_file("/missing/input");
+
+        let err = spawn_error(command);
+        assert!(matches!(err, SpawnError::Redirect(p, _) if p.to_str() == Some("/missing/input")));
+    }
+
+    #[test]
+    fn spawn_detached() {
+        let dir = tempdir::TempDir::new("spawn-detached").unwrap();
+
+        let mut command = Command::new("sh");
+        command.arg("-c").arg("echo $$");
+        command.current_dir(dir.path());
+        command.stdout_file("daemon.log", false);
+
+        let mut spawn = SpawnDetached::new(command);
+        let pid = run(|io| spawn.resume(io)).unwrap();
+
+        // the detached process cannot be waited by its grandparent
+        let null = std::ptr::null_mut();
+        let code = unsafe { libc::waitpid(pid as libc::pid_t, null, libc::WNOHANG) };
+        assert_eq!(-1, code);
+
+        let log = dir.path().join("daemon.log");
+        let mut content = String::new();
+
+        for _ in 0..100 {
+            content = std::fs::read_to_string(&log).unwrap();
+            if !content.is_empty() {
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        }
+
+        assert_eq!(format!("{pid}\n"), content);
+    }
+
+    #[test]
+    fn redirects() {
+        let dir = tempdir::TempDir::new("redirects").unwrap();
+        std::fs::write(dir.path().join("in"), "input\n").unwrap();
+
+        let mut command = Command::new("sh");
+        command
+            .current_dir(dir.path())
+            .arg("-c")
+            .arg("cat; echo err >&2");
+        command.stdin_file("in").stdout_file("out", true);
+        command.stderr_file("err", false);
+
+        for _ in 0..2 {
+            let mut spawn = SpawnThenWait::new(command.clone());
+            let output = run(|io| spawn.resume(io)).unwrap();
+            assert!(output.status.success());
+        }
+
+        let out = std::fs::read_to_string(dir.path().join("out")).unwrap();
+        assert_eq!("input\ninput\n", out);
+        let err = std::fs::read_to_string(dir.path().join("err")).unwrap();
+        assert_eq!("err\n", err);
+    }
+
+    fn env_output(mut command: Command) -> Vec<String> {
+        command.stdout(Stdio::piped());
+
+        let mut spawn = SpawnThenWaitWithOutput::new(command);
+        let output = run(|io| spawn.resume(io)).unwrap();
+
+        let mut vars: Vec<_> = String::from_utf8(output.stdout)
+            .unwrap()
+            .lines()
+            .map(ToOwned::to_owned)
+            .collect();
+        vars.sort();
+        vars
+    }
+
+    #[test]
+    fn env_inherit() {
+        let path = std::env::var("PATH").unwrap();
+
+        let mut command = Command::new("env");
+        command.env_inherit(EnvInherit::allowlist(["PATH", "UNDEFINED_VAR"]));
+        command.env("KEY", "val");
+        assert_eq!(
+            vec!["KEY=val".into(), format!("PATH={path}")],
+            env_output(command)
+        );
+
+        let mut command = Command::new("/usr/bin/env");
+        command.env("KEY", "val").env_clear();
+        assert!(env_output(command).is_empty());
+
+        let mut command = Command::new("/usr/bin/env");
+        command.env_minimal().env("KEY", "val");
+        let expected = ["KEY=val", "LC_ALL=C", "PATH=/usr/local/bin:/usr/bin:/bin"];
+        assert_eq!(expected.as_slice(), env_output(command));
+    }
+
+    #[test]
+    fn program_not_found() {
+        let mut command = Command::new("/missing");
+        command.current_dir("/");
+
+        let err = spawn_error(command);
+        assert!(matches!(err, SpawnError::ProgramNotFound(p) if p == "/missing"));
+    }
+
+    #[test]
+    fn interpreter_not_found() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempdir::TempDir::new("interpreter-not-found").unwrap();
+        let script = dir.path().join("script");
+        std::fs::write(&script, "#!/missing/interpreter\n").unwrap();
+        std::fs::set_permissions(&script, PermissionsExt::from_mode(0o755)).unwrap();
+
+        let mut command = Command::new("./script");
+        command.current_dir(dir.path());
+
+        // the program exists, only its interpreter is missing
+        let err = spawn_error_io(command);
+        assert_eq!(std::io::ErrorKind::NotFound, err.kind());
+        assert!(err.get_ref().is_none());
+    }
+
+    #[test]
+    fn umask() {
+        let mut command = Command::new("sh");
+        command.arg("-c").arg("umask").umask(0o027);
+
+        let mut spawn = SpawnThenWaitWithOutput::new(command);
+        let output = run(|io| spawn.resume(io)).unwrap();
+
+        assert_eq!(b"0027\n", output.stdout.as_slice());
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn spawn_pty() {
+        use crate::{
+            coroutines::{ResizePty, SpawnPty},
+            WindowSize,
+        };
+
+        let mut command = Command::new("sh");
+        command.arg("-c").arg("test -t 0 && read line && stty size");
+
+        let mut spawn = SpawnPty::new(command, WindowSize::new(30, 100));
+        let pty = run(|io| spawn.resume(io)).unwrap();
+
+        let mut resize = ResizePty::new(pty, WindowSize::new(40, 120));
+        let mut pty = run(|io| resize.resume(io)).unwrap();
+
+        pty.master.write_all(b"go\n").unwrap();
+
+        // reading from the master fails with EIO once the child exits
+        let mut output = Vec::new();
+        let mut buf = [0; 64];
+        while let Ok(n @ 1..) = pty.master.read(&mut buf) {
+            output.extend_from_slice(&buf[..n]);
+        }
+
+        let mut wait = Wait::new(pty.child);
+        let status = run(|io| wait.resume(io)).unwrap().status;
+
+        assert!(status.success());
+        assert_eq!(b"go\r\n40 120\r\n", output.as_slice());
+    }
+
+    fn spawn_piped(script: &str) -> Child {
+        let mut command = Command::new("sh");
+        command.arg("-c").arg(script);
+        command.stdin(Stdio::piped());
+        command.stdout(Stdio::piped());
+        command.stderr(Stdio::piped());
+
+        let mut spawn = Spawn::new(command);
+        run(|io| spawn.resume(io)).unwrap()
+    }
+
+    fn expect(child: Child, steps: Vec<ExpectStep>) -> ExpectOutput {
+        let mut expect = Expect::new(child, steps);
+        run(|io| expect.resume(io)).unwrap()
+    }
+
+    #[test]
+    fn expect_script() {
+        let child =
+            spawn_piped("printf 'Passphrase: ' >&2; read p; echo \"got $p\"; exec sleep 60");
+        let timeout = Duration::from_secs(5);
+
+        let steps = vec![
+            ExpectStep::new("Passphrase:", "secret\n").timeout(timeout),
+            ExpectStep::new("got secret", "").timeout(timeout),
+        ];
+
+        let ExpectOutput::Done(child) = expect(child, steps) else {
+            panic!("expect script should succeed");
+        };
+
+        let steps = vec![ExpectStep::new("never", "").timeout(Duration::from_millis(100))];
+
+        let ExpectOutput::Timeout(mut child, 0) = expect(child, steps) else {
+            panic!("expect script should time out");
+        };
+
+        child.process.kill().unwrap();
+
+        let steps = vec![ExpectStep::new("never", "")];
+
+        let ExpectOutput::Eof(mut child, 0) = expect(child, steps) else {
+            panic!("expect script should reach end of file");
+        };
+
+        child.process.wait().unwrap();
+    }
+
+    fn terminate(mut child: Child, policy: TerminationPolicy) -> TerminateOutput {
+        let mut stdout = BufReader::new(child.process.stdout.take().unwrap());
+        let mut line = String::new();
+        stdout.read_line(&mut line).unwrap();
+        assert_eq!("ready\n", line);
+
+        let mut terminate = Terminate::new(child, policy);
+        run(|io| terminate.resume(io)).unwrap()
+    }
+
+    #[test]
+    fn terminate_graceful() {
+        let child = spawn_piped("trap 'exit 3' TERM; echo ready; while :; do sleep 0.1; done");
+        let output = terminate(child, TerminationPolicy::default());
+
+        assert_eq!(Termination::Graceful, output.termination);
+        assert_eq!(Some(3), output.output.status.code());
+    }
+
+    #[test]
+    fn terminate_escalated() {
+        #[allow(unused_mut)]
+        let mut child = spawn_piped("trap '' TERM; echo ready; while :; do sleep 0.1; done");
+        // exit is then checked periodically instead of being polled
+        #[cfg(target_os = "linux")]
+        {
+            child.pidfd = None;
+        }
+
+        let policy = TerminationPolicy::default().grace(Duration::from_millis(200));
+        let output = terminate(child, policy);
+
+        assert_eq!(Termination::Escalated, output.termination);
+        assert_eq!(Some(libc::SIGKILL), output.output.status.signal());
+    }
+
+    #[test]
+    fn terminate_timeout() {
+        let child = spawn_piped("echo ready");
+
+        let policy = TerminationPolicy::default();
+        let mut terminate = Terminate::new(child, policy).timeout(Duration::from_secs(5));
+        let output = run(|io| terminate.resume(io)).unwrap();
+
+        assert_eq!(Termination::Exited, output.termination);
+        assert!(output.output.status.success());
+    }
+
+    fn wait_ready(child: Child, probe: Probe) -> WaitReadyOutput {
+        let policy = TerminationPolicy::default().grace(Duration::from_millis(100));
+        let timeout = Duration::from_millis(500);
+        let wait_ready = WaitReady::new(child, probe, timeout).termination(policy);
+        resume_wait_ready(wait_ready)
+    }
+
+    fn resume_wait_ready(mut wait_ready: WaitReady) -> WaitReadyOutput {
+        run(|io| wait_ready.resume(io)).unwrap()
+    }
+
+    #[test]
+    fn wait_ready_line() {
+        let child =
+            spawn_piped("sleep 0.1; printf 'starting\\nlistening on :8080\\nrest'; sleep 60");
+        let WaitReadyOutput::Ready(mut child, rest) = wait_ready(child, Probe::line("listening"))
+        else {
+            panic!("child should be ready");
+        };
+
+        assert_eq!(b"rest", rest.as_slice());
+        assert!(!child.kill_on_drop);
+
+        child.process.kill().unwrap();
+        child.process.wait().unwrap();
+
+        let child = spawn_piped("echo starting; exit 2");
+        let WaitReadyOutput::Exited(output) = wait_ready(child, Probe::line("listening")) else {
+            panic!("child should have exited");
+        };
+
+        assert_eq!(Some(2), output.status.code());
+    }
+
+    #[test]
+    fn wait_ready_path() {
+        let dir = tempdir::TempDir::new("wait-ready").unwrap();
+        let path = dir.path().join("ready");
+        let script = format!("sleep 0.1; touch {}; sleep 60", path.display());
+
+        let child = spawn_piped(&script);
+        let WaitReadyOutput::Ready(mut child, _) = wait_ready(child, Probe::file(&path)) else {
+            panic!("child should be ready");
+        };
+
+        child.process.kill().unwrap();
+        child.process.wait().unwrap();
+
+        // relative paths are resolved against the given directory
+        let mut command = Command::new("sh");
+        command
+            .current_dir(dir.path())
+            .arg("-c")
+            .arg("sleep 0.1; touch relative; sleep 60");
+
+        let mut spawn = Spawn::new(command);
+        let child = run(|io| spawn.resume(io)).unwrap();
+
+        let timeout = Duration::from_millis(500);
+        let relative = WaitReady::new(child, Probe::file("relative"), timeout);
+        let relative = relative.current_dir(dir.path());
+        let WaitReadyOutput::Ready(mut child, _) = resume_wait_ready(relative) else {
+            panic!("child should be ready");
+        };
+
+        child.process.kill().unwrap();
+        child.process.wait().unwrap();
+
+        // the file is not a socket
+        let child = spawn_piped("sleep 60");
+        let WaitReadyOutput::Timeout(output) = wait_ready(child, Probe::socket(&path)) else {
+            panic!("child should have timed out");
+        };
+
+        assert_eq!(Termination::Graceful, output.termination);
+        assert_eq!(Some(libc::SIGTERM), output.output.status.signal());
+    }
+
+    #[test]
+    fn wait_ready_drop() {
+        let child = spawn_piped("sleep 60");
+        let pid = child.id() as libc::pid_t;
+
+        // dropped on a failed I/O, the child process is killed
+        let mut wait_ready = WaitReady::new(child, Probe::file("ready"), Duration::from_secs(5));
+        let Err(crate::Io::Stat(_)) = wait_ready.resume(None) else {
+            panic!("path should be checked");
+        };
+
+        drop(wait_ready);
+
+        for _ in 0..100 {
+            if unsafe { libc::kill(pid, 0) } == -1 {
+                break;
+            }
+
+            std::thread::sleep(Duration::from_millis(10));
+        }
+
+        let err = std::io::Error::last_os_error;
+        assert_eq!(-1, unsafe { libc::kill(pid, 0) });
+        assert_eq!(Some(libc::ESRCH), err().raw_os_error());
+    }
+
+    #[test]
+    fn retry() {
+        let dir = tempdir::TempDir::new("retry").unwrap();
+
+        // fails with EX_TEMPFAIL until the third attempt
+        let dir_path = dir.path().to_owned();
+        let command = move || {
+            let mut command = Command::new("sh");
+            command.current_dir(&dir_path).arg("-c").arg(
+                "n=$(cat count 2>/dev/null || echo 0); n=$((n + 1)); echo $n > count; echo attempt $n; [ $n -ge 3 ] || exit 75",
+            );
+            command.stdout(Stdio::piped());
+            command
+        };
+
+        let delay = Duration::from_millis(10);
+        let policy = RetryPolicy::default()
+            .max_attempts(5)
+            .backoff(delay, delay, 1.0)
+            .exit_codes([75]);
+
+        let spawn =
+            move || SpawnThenWaitWithOutput::new(command()).limit(OutputLimit::keep_tail(2));
+        let mut retry = Retry::new(spawn, policy);
+        let output = run(|io| retry.resume(io)).unwrap();
+
+        // each attempt keeps its stdio and output limit
+        assert!(output.status.success());
+        assert_eq!(b"3\n", output.stdout.as_slice());
+        assert_eq!(8, output.stdout_discarded);
+
+        let count = std::fs::read_to_string(dir.path().join("count")).unwrap();
+        assert_eq!("3\n", count);
+    }
+
+    #[test]
+    fn supervise() {
+        let command = || {
+            let mut command = Command::new("sh");
+            command.arg("-c").arg("exit 1");
+            command
+        };
+
+        let millis = Duration::from_millis;
+        let policy = RestartPolicy::default()
+            .max_restarts(2, Duration::from_secs(60))
+            .backoff(millis(10), millis(20), 2.0);
+
+        let mut supervise = Supervise::new(command, policy);
+        let mut events = Vec::new();
+
+        while let Some(event) = run(|io| supervise.resume(io)).unwrap() {
+            events.push(match event {
+                SuperviseEvent::Spawned(_) => "spawned".to_owned(),
+                SuperviseEvent::Exited(output) => format!("exited {}", output.status),
+                SuperviseEvent::Restarting(delay) => format!("restarting in {delay:?}"),
+                SuperviseEvent::GaveUp => "gave up".to_owned(),
+            });
+        }
+
+        let expected = [
+            "spawned",
+            "exited exit status: 1",
+            "restarting in 10ms",
+            "spawned",
+            "exited exit status: 1",
+            "restarting in 20ms",
+            "spawned",
+            "exited exit status: 1",
+            "gave up",
+        ];
+
+        assert_eq!(expected.as_slice(), events);
+    }
+
+    #[test]
+    fn supervise_stop() {
+        let command = || {
+            let mut command = Command::new("sh");
+            command.arg("-c").arg("exit 1");
+            command
+        };
+
+        let millis = Duration::from_millis;
+        let policy = RestartPolicy::default().backoff(millis(10), millis(10), 1.0);
+
+        let mut supervise = Supervise::new(command, policy);
+
+        loop {
+            match run(|io| supervise.resume(io)).unwrap() {
+                Some(SuperviseEvent::Restarting(_)) => break,
+                event => assert!(event.is_some()),
+            }
+        }
+
+        // stopped while the backoff sleep is pending
+        let Err(io) = supervise.resume(None) else {
+            panic!("backoff should sleep");
+        };
+
+        assert!(supervise.stop().is_none());
+
+        let arg = Some(handle(io).unwrap());
+        assert!(matches!(supervise.resume(arg), Ok(None)));
+        assert!(matches!(supervise.resume(None), Ok(None)));
+    }
+
+    #[test]
+    fn job_pool_spawn_error() {
+        let mut command = Command::new("sleep");
+        command.arg("60");
+        let commands = [command, Command::new("/missing")];
+
+        let mut arg = None;
+        let mut pool = JobPool::new(commands, 2);
+        let mut pids = Vec::new();
+
+        let err = loop {
+            let io = pool.resume(arg.take()).unwrap_err();
+
+            match handle(io) {
+                Ok(crate::Io::Spawn(Ok(child))) => {
+                    pids.push(child.id() as libc::pid_t);
+                    arg = Some(crate::Io::Spawn(Ok(child)));
+                }
+                Ok(io) => arg = Some(io),
+                Err(err) => break err,
+            }
+        };
+
+        assert_eq!(std::io::ErrorKind::NotFound, err.kind());
+
+        // the running job is killed and reaped with the pool
+        drop(pool);
+
+        let err = std::io::Error::last_os_error;
+        assert_eq!(1, pids.len());
+        assert_eq!(-1, unsafe { libc::kill(pids[0], 0) });
+        assert_eq!(Some(libc::ESRCH), err().raw_os_error());
+    }
+
+    #[test]
+    fn job_pool() {
+        let commands = ["0.3", "0.1", "0", "0", "0"].map(|delay| {
+            let mut command = Command::new("sh");
+            command
+                .arg("-c")
+                .arg(format!("sleep {delay}; echo {delay}"));
+            command
+        });
+
+        let mut pool = JobPool::new(commands, 2);
+        let mut results = Vec::new();
+
+        while let Some(result) = run(|io| pool.resume(io)).unwrap() {
+            results.push(result);
+        }
+
+        let indexes: Vec<_> = results.iter().map(|(index, _)| *index).collect();
+        assert_eq!(5, indexes.len());
+        assert_eq!(1, indexes[0]);
+        assert_eq!(0, indexes[4]);
+
+        for (index, output) in results {
+            assert!(output.status.success());
+            let expected = ["0.3\n", "0.1\n", "0\n", "0\n", "0\n"][index];
+            assert_eq!(expected.as_bytes(), output.stdout.as_slice());
+
+            if index == 0 {
+                assert!(output.usage.duration >= Duration::from_millis(300));
+            }
+        }
+    }
+
+    #[test]
+    fn xargs() {
+        let args: Vec<_> = (0..1000).map(|n| n.to_string()).collect();
+        let expected: String = args.iter().map(|arg| format!("{arg}\n")).collect();
+
+        let command = || {
+            let mut command = Command::new("printf");
+            command.arg("%s\\n");
+            command
+        };
+
+        let mut xargs = Xargs::new(command, &args).max_args(300).parallel(2);
+        let output = run(|io| xargs.resume(io)).unwrap();
+
+        assert!(output.success());
+        assert_eq!(4, output.batches.len());
+        assert_eq!(expected.as_bytes(), output.stdout().as_slice());
+    }
+
+    fn capture(script: &str, capture: Capture) -> std::io::Result<crate::CaptureOutput> {
+        let mut command = Command::new("sh");
+        command.arg("-c").arg(script);
+
+        let mut spawn = SpawnThenCapture::new(command, capture);
+        run(|io| spawn.resume(io))
+    }
+
+    #[test]
+    fn spawn_then_capture() {
+        let script = "seq 1 100000; echo err >&2";
+        let limit = Capture::new()
+            .stdout_limit(OutputLimit::keep_tail(13))
+            .stderr_limit(OutputLimit::keep_head(2));
+        let output = capture(script, limit).unwrap();
+
+        assert!(output.status.success());
+        assert_eq!(b"99999\n100000\n", output.stdout.bytes.as_slice());
+        assert!(output.stdout.is_truncated());
+        assert_eq!(b"er", output.stderr.bytes.as_slice());
+        assert_eq!(2, output.stderr.discarded);
+
+        // a runaway child is killed as soon as it exceeds the limit
+        let limit = Capture::new().limit(OutputLimit::error(1024));
+        let err = capture("exec yes", limit).unwrap_err();
+        let err = err.get_ref().unwrap().downcast_ref::<CaptureError>();
+        assert!(matches!(
+            err,
+            Some(CaptureError::LimitExceeded(Stream::Stdout, 1024))
+        ));
+    }
+
+    #[test]
+    fn spawn_then_capture_group() {
+        // the background sleep would hold the pipes open if only the
+        // group leader was killed
+        let mut command = Command::new("sh");
+        command.arg("-c").arg("sleep 10 & exec yes");
+        command.process_group(0);
+
+        let started = Instant::now();
+        let limit = Capture::new().limit(OutputLimit::error(1024));
+        let mut spawn = SpawnThenCapture::new(command, limit);
+        let err = run(|io| spawn.resume(io)).unwrap_err();
+
+        assert_eq!(std::io::ErrorKind::Other, err.kind());
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+
+    #[test]
+    fn spawn_then_wait_with_output_limit() {
+        let mut command = Command::new("sh");
+        command.arg("-c").arg("seq 1 100000; echo error >&2");
+        command.stdout(Stdio::piped());
+
+        let mut spawn = SpawnThenWaitWithOutput::new(command)
+            .stdout_limit(OutputLimit::keep_tail(7))
+            .stderr_limit(OutputLimit::keep_head(3));
+        let output = run(|io| spawn.resume(io)).unwrap();
+
+        assert!(output.status.success());
+        assert!(output.is_truncated());
+        assert_eq!(b"100000\n", output.stdout.as_slice());
+        assert_eq!(588_888, output.stdout_discarded);
+        assert_eq!(b"err", output.stderr.as_slice());
+        assert_eq!(3, output.stderr_discarded);
+
+        let mut command = Command::new("yes");
+        command.stdout(Stdio::piped());
+
+        let mut spawn = SpawnThenWaitWithOutput::new(command).limit(OutputLimit::error(1024));
+        let err = run(|io| spawn.resume(io)).unwrap_err();
+
+        assert_eq!(std::io::ErrorKind::Other, err.kind());
+        let err = err.get_ref().unwrap().downcast_ref::<CaptureError>();
+        assert!(matches!(
+            err,
+            Some(CaptureError::LimitExceeded(Stream::Stdout, 1024))
+        ));
+    }
+
+    #[test]
+    fn spawn_then_capture_merged() {
+        let script = "echo a; echo b >&2; echo c";
+        let output = capture(script, Capture::new().mode(CaptureMode::Merged)).unwrap();
+
+        assert_eq!(b"a\nb\nc\n", output.stdout.bytes.as_slice());
+        assert!(output.stderr.bytes.is_empty());
+        assert!(output.chunks.is_empty());
+
+        let script = "echo a; sleep 0.1; echo b >&2; sleep 0.1; echo c";
+        let output = capture(script, Capture::new().mode(CaptureMode::Tagged)).unwrap();
+
+        assert_eq!(b"a\nb\nc\n", output.stdout.bytes.as_slice());
+        let chunks = [
+            (Stream::Stdout, "a\n"),
+            (Stream::Stderr, "b\n"),
+            (Stream::Stdout, "c\n"),
+        ]
+        .map(|(stream, bytes)| Chunk {
+            stream,
+            bytes: bytes.into(),
+        });
+        assert_eq!(chunks.as_slice(), output.chunks.as_slice());
+    }
+
+    #[test]
+    fn spawn_then_capture_tee() {
+        let sink = Arc::new(Mutex::new(Vec::new()));
+        let config = Capture::new()
+            .tee(Tee::Sink(sink.clone()))
+            .stdout_limit(OutputLimit::keep_tail(2));
+        let output = capture("echo abc; sleep 0.1; echo def >&2", config).unwrap();
+
+        assert_eq!(b"c\n", output.stdout.bytes.as_slice());
+        assert_eq!(b"def\n", output.stderr.bytes.as_slice());
+
+        let forwarded = sink.lock().unwrap();
+        assert_eq!(b"abc\ndef\n", forwarded.as_slice());
+    }
+
+    #[test]
+    fn spawn_then_capture_tee_error() {
+        struct Broken;
+
+        impl std::io::Write for Broken {
+            fn write(&mut self, _: &[u8]) -> std::io::Result<usize> {
+                Err(std::io::Error::other("broken sink"))
+            }
+
+            fn flush(&mut self) -> std::io::Result<()> {
+                Ok(())
+            }
+        }
+
+        // the child process is killed instead of being waited forever
+        let config = Capture::new().tee(Tee::sink(Broken));
+        let err = capture("exec yes", config).unwrap_err();
+        assert_eq!("broken sink", err.to_string());
+    }
+
+    #[test]
+    fn usage() {
+        let mut command = Command::new("sh");
+        command.arg("-c").arg("sleep 0.2");
+
+        let mut spawn = SpawnThenWait::new(command);
+        let output = run(|io| spawn.resume(io)).unwrap();
+
+        let usage = output.usage;
+        assert!(usage.duration >= Duration::from_millis(200));
+        assert!(usage.started < usage.ended);
+
+        #[cfg(target_os = "linux")]
+        {
+            assert!(usage.user_time.is_some());
+            assert!(usage.system_time.is_some());
+            assert!(usage.max_rss.unwrap() > 0);
+        }
+
+        let mut command = Command::new("sh");
+        command.arg("-c").arg("sleep 0.2");
+
+        let mut spawn = SpawnThenWaitWithOutput::new(command);
+        let output = run(|io| spawn.resume(io)).unwrap();
+
+        assert!(output.usage.duration >= Duration::from_millis(200));
+        #[cfg(target_os = "linux")]
+        assert!(output.usage.max_rss.unwrap() > 0);
+    }
+
+    #[cfg(feature = "tracing")]
+    #[test]
+    fn tracing() {
+        use tracing::{
+            field::{Field, Visit},
+            span, Event, Metadata, Subscriber,
+        };
+
+        /// Records span names, fields and events as strings.
+        struct Recorder(Arc<Mutex<Vec<String>>>);
+
+        impl Visit for Recorder {
+            fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+                let mut log = self.0.lock().unwrap();
+                log.push(format!("{}={value:?}", field.name()));
+            }
+        }
+
+        impl Subscriber for Recorder {
+            fn enabled(&self, _: &Metadata<'_>) -> bool {
+                true
+            }
+
+            fn new_span(&self, span: &span::Attributes<'_>) -> span::Id {
+                self.0.lock().unwrap().push(span.metadata().name().into());
+                span.record(&mut Recorder(self.0.clone()));
+                span::Id::from_u64(1)
+            }
+
+            fn record(&self, _: &span::Id, values: &span::Record<'_>) {
+                values.record(&mut Recorder(self.0.clone()));
+            }
+
+            fn record_follows_from(&self, _: &span::Id, _: &span::Id) {}
+
+            fn event(&self, event: &Event<'_>) {
+                event.record(&mut Recorder(self.0.clone()));
+            }
+
+            fn enter(&self, _: &span::Id) {}
+
+            fn exit(&self, _: &span::Id) {}
+        }
+
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let recorder = Recorder(log.clone());
+
+        let pid = tracing::subscriber::with_default(recorder, || {
+            let mut command = Command::new("sh");
+            command.arg("-c").arg("exit 3").sensitive_arg("secret");
+
+            let mut spawn = Spawn::new(command);
+            let Ok(crate::Io::Spawn(Ok(child))) = handle(spawn.resume(None).unwrap_err()) else {
+                panic!("child should be spawned");
+            };
+
+            let pid = child.id();
+            let mut wait = Wait::new(child);
+            handle(wait.resume(None).unwrap_err()).unwrap();
+            pid
+        });
+
+        let log = log.lock().unwrap();
+        assert_eq!("process", log[0]);
+        assert!(log.contains(&String::from("program=\"sh\"")));
+        assert!(log.contains(&String::from(
+            r#"args=Some(["-c", "exit 3", "<redacted>"])"#
+        )));
+        assert!(log.contains(&format!("pid={pid}")));
+        assert!(log.contains(&String::from("message=spawned process")));
+        assert!(log.contains(&String::from("status=exit status: 3")));
+        assert!(log.contains(&String::from("message=process exited")));
+    }
+}
@@ -1,10 +1,46 @@
 //! Module dedicated to the Tokio-based, async runtime.
 
-use std::{io, process::Output};
+use std::{io, process::Stdio, time::Duration};
 
-use tokio::process::Command as TokioCommand;
+use tokio::{
+    process::{ChildStderr, ChildStdout, Command as TokioCommand},
+    task, time,
+};
 
-use crate::{Command, Io, SpawnOutput};
+#[cfg(target_os = "linux")]
+use std::{
+    fs::File,
+    io::{Read, Write},
+    pin::Pin,
+    task::{ready, Context},
+};
+#[cfg(unix)]
+use std::{
+    fs::FileType,
+    future::{self, Future},
+    os::fd::AsRawFd,
+    path::PathBuf,
+    task::Poll,
+};
+
+#[cfg(unix)]
+use tokio::io::{unix::AsyncFd, Interest};
+use tokio::io::{AsyncRead, AsyncReadExt};
+#[cfg(target_os = "linux")]
+use tokio::io::{AsyncWrite, ReadBuf};
+
+use crate::{
+    Capture, CaptureError, CaptureOutput, Captured, Child, Command, Io, Output, OutputLimit,
+    SpawnOutput, Stream,
+};
+#[cfg(unix)]
+use crate::{Job, ReadOutput, Signal};
+#[cfg(target_os = "linux")]
+use crate::{Pty, WindowSize};
+
+use super::shared;
+#[cfg(feature = "tracing")]
+use super::trace;
 
 /// The main runtime I/O handler.
 ///
@@ -23,6 +59,30 @@ pub async fn handle(io: Io) -> io::Result<Io> {
 
         Io::SpawnThenWait(io) => spawn_then_wait(io).await,
         Io::SpawnThenWaitWithOutput(io) => spawn_then_wait_with_output(io).await,
+        Io::SpawnThenCapture(io) => spawn_then_capture(io).await,
+        Io::Sleep(io) => sleep(io).await,
+        Io::Spawn(io) => spawn(io),
+        Io::Wait(io) => wait(io).await,
+        #[cfg(unix)]
+        Io::SpawnDetached(io) => spawn_detached(io).await,
+        #[cfg(unix)]
+        Io::Kill(io) => kill(io),
+        #[cfg(unix)]
+        Io::WaitTimeout(io) => wait_timeout(io).await,
+        #[cfg(unix)]
+        Io::Read(io) => read(io).await,
+        #[cfg(unix)]
+        Io::Write(io) => write(io).await,
+        #[cfg(unix)]
+        Io::WaitAny(io) => wait_any(io).await,
+        #[cfg(unix)]
+        Io::Stat(io) => stat(io).await,
+        #[cfg(unix)]
+        Io::ArgMax(io) => arg_max(io),
+        #[cfg(target_os = "linux")]
+        Io::SpawnPty(io) => spawn_pty(io),
+        #[cfg(target_os = "linux")]
+        Io::ResizePty(io) => resize_pty(io),
     }
 }
 
@@ -32,48 +92,690 @@ pub async fn handle(io: Io) -> io::Result<Io> {
 /// command builder, spawns a process, collects std{in,out,err} then
 /// waits for the exit status.
 pub async fn spawn_then_wait(input: Result<SpawnOutput, Command>) -> io::Result<Io> {
-    let Err(command) = input else {
+    let Err(mut command) = input else {
         let kind = io::ErrorKind::InvalidInput;
         return Err(io::Error::new(kind, "missing command"));
     };
 
-    let mut command = TokioCommand::from(command);
-    let mut child = command.spawn()?;
+    shared::check_current_dir(&command)?;
+    shared::open_redirects(&mut command)?;
+    let map_err = shared::map_spawn_error(&command);
 
-    let stdin = child.stdin.take().and_then(|io| io.into_owned_fd().ok());
-    let stdout = child.stdout.take().and_then(|io| io.into_owned_fd().ok());
-    let stderr = child.stderr.take().and_then(|io| io.into_owned_fd().ok());
+    #[cfg(feature = "tracing")]
+    let span = trace::span(&command);
 
-    let output = SpawnOutput {
-        status: child.wait().await?,
-        stdin: stdin.map(Into::into),
-        stdout: stdout.map(Into::into),
-        stderr: stderr.map(Into::into),
-    };
+    let mut command = TokioCommand::from(command).into_std();
+    let child = Child::new(command.spawn().map_err(map_err)?);
+
+    #[cfg(target_os = "linux")]
+    let pidfd = super::unix::pidfd_open(child.id());
+    #[cfg(target_os = "linux")]
+    let child = child.with_pidfd(pidfd);
 
-    Ok(Io::SpawnThenWait(Ok(output)))
+    #[cfg(feature = "tracing")]
+    let child = trace::attach(child, span);
+
+    Ok(Io::SpawnThenWait(Ok(wait_child(child).await?)))
 }
 
 /// Spawns a process then wait for its child's output.
 ///
-/// This function builds a [`std::process::Command`] from the flow's
-/// command builder, spawns a process, then waits for the output.
-pub async fn spawn_then_wait_with_output(input: Result<Output, Command>) -> io::Result<Io> {
-    let Err(command) = input else {
+/// This function builds a [`tokio::process::Command`] from the
+/// flow's command builder, spawns a process using its standard
+/// counterpart, then reads its stdout and stderr within their
+/// optional limit before waiting for it, using the Tokio reactor.
+pub async fn spawn_then_wait_with_output(
+    input: Result<Output, (Command, Option<OutputLimit>, Option<OutputLimit>)>,
+) -> io::Result<Io> {
+    let Err((mut command, stdout_limit, stderr_limit)) = input else {
         let kind = io::ErrorKind::InvalidInput;
         return Err(io::Error::new(kind, "missing command"));
     };
 
-    let mut command = TokioCommand::from(command);
-    let output = command.output().await?;
+    shared::check_current_dir(&command)?;
+    shared::open_redirects(&mut command)?;
+    shared::default_output_stdio(&mut command);
+    let map_err = shared::map_spawn_error(&command);
+
+    #[cfg(unix)]
+    let group = command.get_process_group();
+
+    #[cfg(feature = "tracing")]
+    let span = trace::span(&command);
+
+    let mut command = TokioCommand::from(command).into_std();
+    let mut process = command.spawn().map_err(map_err)?;
+
+    drop(process.stdin.take());
+    let stdout = process.stdout.take().map(ChildStdout::from_std);
+    let stderr = process.stderr.take().map(ChildStderr::from_std);
+    let child = Child::new(process);
+
+    #[cfg(unix)]
+    let child = child.with_group(group);
+
+    #[cfg(target_os = "linux")]
+    let pidfd = super::unix::pidfd_open(child.id());
+    #[cfg(target_os = "linux")]
+    let child = child.with_pidfd(pidfd);
+
+    #[cfg(feature = "tracing")]
+    let child = trace::attach(child, span);
+
+    let stdout = read_limited(stdout.transpose(), Stream::Stdout, stdout_limit);
+    let stderr = read_limited(stderr.transpose(), Stream::Stderr, stderr_limit);
+    let result = tokio::try_join!(stdout, stderr);
+
+    #[cfg(unix)]
+    if result.is_err() {
+        // errors mean that the child process already exited
+        let _ = super::unix::kill(&child, Signal::KILL);
+    }
+
+    let SpawnOutput { status, usage, .. } = wait_child(child).await?;
+    let (stdout, stderr) = result?;
+    let output = Output {
+        status,
+        stdout: stdout.bytes,
+        stderr: stderr.bytes,
+        stdout_discarded: stdout.discarded,
+        stderr_discarded: stderr.discarded,
+        usage,
+    };
 
     Ok(Io::SpawnThenWaitWithOutput(Ok(output)))
 }
 
-/// Converts a [`Command`] builder to a [`std::process::Command`].
+/// Reads the given stream until the end of file, retaining at most
+/// the given limit of bytes.
+///
+/// Fails as soon as the stream exceeds the limit with the
+/// [`crate::OverflowPolicy::Error`] policy.
+async fn read_limited(
+    reader: io::Result<Option<impl AsyncRead + Unpin>>,
+    stream: Stream,
+    limit: Option<OutputLimit>,
+) -> io::Result<Captured> {
+    let mut buffer = shared::CaptureBuffer::new(limit);
+
+    if let Some(mut reader) = reader? {
+        let mut chunk = [0; 8192];
+
+        loop {
+            let n = match reader.read(&mut chunk).await {
+                Ok(0) => break,
+                Ok(n) => n,
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                Err(err) => return Err(err),
+            };
+
+            if let Err(max) = buffer.push(stream, &chunk[..n]) {
+                return Err(CaptureError::LimitExceeded(stream, max).into());
+            }
+        }
+    }
+
+    Ok(buffer.into_captured().0)
+}
+
+/// Spawns a process then captures its output.
+///
+/// This function builds a [`tokio::process::Command`] from the
+/// flow's command builder, spawns a process using its standard
+/// counterpart, then reads its stdout and stderr within the limits
+/// of the capture configuration, in a blocking task.
+pub async fn spawn_then_capture(
+    input: Result<CaptureOutput, (Command, Capture)>,
+) -> io::Result<Io> {
+    let Err((mut command, capture)) = input else {
+        let kind = io::ErrorKind::InvalidInput;
+        return Err(io::Error::new(kind, "missing command"));
+    };
+
+    shared::check_current_dir(&command)?;
+    shared::open_redirects(&mut command)?;
+    let map_err = shared::map_spawn_error(&command);
+
+    if command.stdin.is_none() {
+        command.stdin(Stdio::null());
+    }
+
+    #[cfg(unix)]
+    let group = command.get_process_group();
+    #[cfg(not(unix))]
+    let group = None;
+
+    #[cfg(feature = "tracing")]
+    let span = trace::span(&command);
+
+    let command = TokioCommand::from(command).into_std();
+    let output = task::spawn_blocking(move || {
+        #[cfg(feature = "tracing")]
+        let _span = span.entered();
+        shared::spawn_then_capture(command, capture, group)
+    });
+    let output = output.await?.map_err(map_err)?;
+
+    Ok(Io::SpawnThenCapture(Ok(output)))
+}
+
+/// Sleeps for the requested duration.
+pub async fn sleep(input: Result<(), Duration>) -> io::Result<Io> {
+    let Err(duration) = input else {
+        let kind = io::ErrorKind::InvalidInput;
+        return Err(io::Error::new(kind, "missing duration"));
+    };
+
+    time::sleep(duration).await;
+
+    Ok(Io::Sleep(Ok(())))
+}
+
+/// Spawns a process without waiting for it.
+///
+/// This function builds a [`tokio::process::Command`] from the
+/// flow's command builder, then spawns a process using its standard
+/// counterpart, so that the returned [`Child`] handle remains
+/// runtime-agnostic.
+pub fn spawn(input: Result<Child, Command>) -> io::Result<Io> {
+    let Err(mut command) = input else {
+        let kind = io::ErrorKind::InvalidInput;
+        return Err(io::Error::new(kind, "missing command"));
+    };
+
+    shared::check_current_dir(&command)?;
+    shared::open_redirects(&mut command)?;
+    let map_err = shared::map_spawn_error(&command);
+
+    let kill_on_drop = command.kill_on_drop;
+
+    #[cfg(unix)]
+    let group = command.get_process_group();
+
+    #[cfg(feature = "tracing")]
+    let span = trace::span(&command);
+
+    let mut command = TokioCommand::from(command).into_std();
+    let child = Child::new(command.spawn().map_err(map_err)?).with_kill_on_drop(kill_on_drop);
+
+    #[cfg(feature = "tracing")]
+    let child = trace::attach(child, span);
+
+    #[cfg(unix)]
+    let child = child.with_group(group);
+
+    #[cfg(target_os = "linux")]
+    let pidfd = super::unix::pidfd_open(child.id());
+    #[cfg(target_os = "linux")]
+    let child = child.with_pidfd(pidfd);
+
+    Ok(Io::Spawn(Ok(child)))
+}
+
+/// Spawns a detached process, which outlives its parent.
+///
+/// This function builds a [`tokio::process::Command`] from the
+/// flow's command builder, redirects std{in,out,err} not configured
+/// to `/dev/null`, then spawns a process using its standard
+/// counterpart in a new session. The process forks again then exits,
+/// and the ID of the grandchild is returned.
+#[cfg(unix)]
+pub async fn spawn_detached(input: Result<u32, Command>) -> io::Result<Io> {
+    let Err(mut command) = input else {
+        let kind = io::ErrorKind::InvalidInput;
+        return Err(io::Error::new(kind, "missing command"));
+    };
+
+    shared::check_current_dir(&command)?;
+    shared::open_redirects(&mut command)?;
+    shared::default_null_stdio(&mut command);
+    let map_err = shared::map_spawn_error(&command);
+
+    // the process already runs in a new session
+    command.setsid = false;
+    command.process_group = None;
+
+    #[cfg(feature = "tracing")]
+    let span = trace::span(&command);
+
+    let command = TokioCommand::from(command).into_std();
+    let pid = task::spawn_blocking(move || super::unix::spawn_detached(command));
+    let pid = pid.await?.map_err(map_err)?;
+
+    #[cfg(feature = "tracing")]
+    trace::spawned(&span, Some(pid));
+
+    Ok(Io::SpawnDetached(Ok(pid)))
+}
+
+/// Waits for a spawned child process' exit status.
+///
+/// This function collects std{in,out,err} of the given child process
+/// then waits for the exit status using the Tokio reactor.
+pub async fn wait(input: Result<SpawnOutput, Child>) -> io::Result<Io> {
+    let Err(child) = input else {
+        let kind = io::ErrorKind::InvalidInput;
+        return Err(io::Error::new(kind, "missing child"));
+    };
+
+    Ok(Io::Wait(Ok(wait_child(child).await?)))
+}
+
+/// Waits for a spawned child process' exit status, for at most the
+/// given duration.
+///
+/// The child process is given back if it is still running once the
+/// timeout expires.
+#[cfg(unix)]
+pub async fn wait_timeout(
+    input: Result<Result<SpawnOutput, Child>, (Child, Duration)>,
+) -> io::Result<Io> {
+    let Err((child, timeout)) = input else {
+        let kind = io::ErrorKind::InvalidInput;
+        return Err(io::Error::new(kind, "missing child"));
+    };
+
+    let output = match time::timeout(timeout, exited(&child)).await {
+        Ok(exited) => exited.and_then(|()| shared::wait_child(child)).map(Ok)?,
+        Err(_) => Err(child),
+    };
+
+    Ok(Io::WaitTimeout(Ok(output)))
+}
+
+/// Waits for the given child process to exit using the Tokio
+/// reactor, then reaps it and collects its std{in,out,err}.
+async fn wait_child(child: Child) -> io::Result<SpawnOutput> {
+    #[cfg(unix)]
+    exited(&child).await?;
+
+    // the child process exited, so reaping it does not block
+    #[cfg(unix)]
+    return shared::wait_child(child);
+
+    #[cfg(not(unix))]
+    task::spawn_blocking(move || shared::wait_child(child)).await?
+}
+
+/// Waits for the given child process to exit, without reaping it.
+///
+/// On Linux, the process file descriptor of the child is registered
+/// in the Tokio reactor when available, otherwise the child process
+/// is checked periodically.
+#[cfg(unix)]
+async fn exited(child: &Child) -> io::Result<()> {
+    #[cfg(target_os = "linux")]
+    if let Some(pidfd) = &child.pidfd {
+        let pidfd = AsyncFd::with_interest(pidfd.as_raw_fd(), Interest::READABLE)?;
+        let _ready = pidfd.readable().await?;
+        return Ok(());
+    }
+
+    while !super::unix::exited(child.id())? {
+        time::sleep(Duration::from_millis(10)).await;
+    }
+
+    Ok(())
+}
+
+/// Queries the type of the file at the given path.
+#[cfg(unix)]
+pub async fn stat(input: Result<Option<FileType>, PathBuf>) -> io::Result<Io> {
+    let Err(path) = input else {
+        let kind = io::ErrorKind::InvalidInput;
+        return Err(io::Error::new(kind, "missing path"));
+    };
+
+    let file_type = task::spawn_blocking(move || shared::stat(&path));
+
+    Ok(Io::Stat(Ok(file_type.await??)))
+}
+
+/// Sends a signal to a spawned child process.
+///
+/// The signal is sent to the whole process group if the child
+/// process leads one.
+#[cfg(unix)]
+pub fn kill(input: Result<Child, (Child, Signal)>) -> io::Result<Io> {
+    let Err((child, signal)) = input else {
+        let kind = io::ErrorKind::InvalidInput;
+        return Err(io::Error::new(kind, "missing child"));
+    };
+
+    super::unix::kill(&child, signal)?;
+
+    #[cfg(feature = "tracing")]
+    trace::killed(&child.span, signal);
+
+    Ok(Io::Kill(Ok(child)))
+}
+
+/// Reads bytes from a spawned child process' output streams.
+///
+/// This function waits for bytes on either the piped stdout or stderr
+/// of the given child process, until the optional timeout, on the
+/// blocking thread pool.
+#[cfg(unix)]
+pub async fn read(input: Result<(Child, ReadOutput), (Child, Option<Duration>)>) -> io::Result<Io> {
+    let Err((mut child, timeout)) = input else {
+        let kind = io::ErrorKind::InvalidInput;
+        return Err(io::Error::new(kind, "missing child"));
+    };
+
+    let output = task::spawn_blocking(move || {
+        let output = super::unix::read(&mut child, timeout)?;
+
+        #[cfg(feature = "tracing")]
+        if output == ReadOutput::Timeout {
+            trace::timed_out(&child.span, timeout);
+        }
+
+        io::Result::Ok((child, output))
+    });
+
+    Ok(Io::Read(Ok(output.await??)))
+}
+
+/// Writes bytes to a spawned child process' stdin, on the blocking
+/// thread pool.
+#[cfg(unix)]
+pub async fn write(input: Result<Child, (Child, Vec<u8>)>) -> io::Result<Io> {
+    let Err((mut child, bytes)) = input else {
+        let kind = io::ErrorKind::InvalidInput;
+        return Err(io::Error::new(kind, "missing child"));
+    };
+
+    let child = task::spawn_blocking(move || {
+        super::unix::write(&mut child, &bytes)?;
+        io::Result::Ok(child)
+    });
+
+    Ok(Io::Write(Ok(child.await??)))
+}
+
+/// Waits for any of the given running jobs to complete.
+///
+/// This function collects the output of all the given jobs until one
+/// of them completes, waiting for their streams and exit using the
+/// Tokio reactor. On error, remaining jobs are killed.
+#[cfg(unix)]
+pub async fn wait_any(input: Result<(Vec<Job>, usize, Output), Vec<Job>>) -> io::Result<Io> {
+    let Err(mut jobs) = input else {
+        let kind = io::ErrorKind::InvalidInput;
+        return Err(io::Error::new(kind, "missing jobs"));
+    };
+
+    // the child process of the completed job exited, so reaping it
+    // does not block
+    match wait_any_job(&mut jobs).await.and_then(shared::wait_job) {
+        Ok((index, output)) => Ok(Io::WaitAny(Ok((jobs, index, output)))),
+        Err(err) => {
+            task::spawn_blocking(move || super::unix::abort_jobs(jobs));
+            Err(err)
+        }
+    }
+}
+
+/// Collects the output of the given jobs until one of them
+/// completes, then removes it from the given jobs.
+///
+/// This is the asynchronous counterpart of
+/// [`super::unix::wait_any`]: pipes and process file descriptors are
+/// registered in the Tokio reactor instead of being polled.
+#[cfg(unix)]
+async fn wait_any_job(jobs: &mut Vec<Job>) -> io::Result<Job> {
+    let mut buf = [0; 8192];
+
+    loop {
+        let mut exiting = false;
+
+        for i in 0..jobs.len() {
+            let process = &jobs[i].child.process;
+
+            if process.stdout.is_some() || process.stderr.is_some() {
+                continue;
+            }
+
+            if super::unix::exited(process.id())? {
+                return Ok(jobs.remove(i));
+            }
+
+            exiting = true;
+        }
+
+        if jobs.is_empty() {
+            let kind = io::ErrorKind::InvalidInput;
+            return Err(io::Error::new(kind, "missing jobs"));
+        }
+
+        let mut streams = Vec::new();
+        let mut fds = Vec::new();
+        let mut exit_pollable = true;
+
+        for (i, job) in jobs.iter().enumerate() {
+            let stdout = job.child.process.stdout.as_ref().map(AsRawFd::as_raw_fd);
+            let stderr = job.child.process.stderr.as_ref().map(AsRawFd::as_raw_fd);
+
+            for (stream, fd) in [(Stream::Stdout, stdout), (Stream::Stderr, stderr)] {
+                if let Some(fd) = fd {
+                    super::unix::set_nonblocking(fd)?;
+                    streams.push((i, Some(stream)));
+                    fds.push(AsyncFd::with_interest(fd, Interest::READABLE)?);
+                }
+            }
+
+            if stdout.is_some() || stderr.is_some() {
+                continue;
+            }
+
+            // process file descriptors become readable once their
+            // child process exits
+            #[cfg(target_os = "linux")]
+            if let Some(pidfd) = &job.child.pidfd {
+                streams.push((i, None));
+                fds.push(AsyncFd::with_interest(
+                    pidfd.as_raw_fd(),
+                    Interest::READABLE,
+                )?);
+                continue;
+            }
+
+            exit_pollable = false;
+        }
+
+        // without process file descriptors, exit of child processes
+        // cannot be awaited, so jobs with closed streams are checked
+        // periodically
+        let mut sleep = match exiting && !exit_pollable {
+            true => Some(Box::pin(time::sleep(Duration::from_millis(10)))),
+            false => None,
+        };
+
+        let ready = future::poll_fn(|cx| {
+            for (n, fd) in fds.iter().enumerate() {
+                if let Poll::Ready(ready) = fd.poll_read_ready(cx) {
+                    return Poll::Ready(ready.map(|_| Some(n)));
+                }
+            }
+
+            match sleep.as_mut().map(|sleep| sleep.as_mut().poll(cx)) {
+                Some(Poll::Ready(())) => Poll::Ready(Ok(None)),
+                _ => Poll::Pending,
+            }
+        });
+
+        let Some(n) = ready.await? else {
+            continue;
+        };
+
+        let (i, stream) = streams[n];
+        let job = &mut jobs[i];
+
+        match stream {
+            Some(Stream::Stdout) => {
+                super::unix::read_into(&mut job.child.process.stdout, &mut job.stdout, &mut buf)?
+            }
+            Some(Stream::Stderr) => {
+                super::unix::read_into(&mut job.child.process.stderr, &mut job.stderr, &mut buf)?
+            }
+            // exited jobs are collected at the next iteration
+            None => (),
+        }
+    }
+}
+
+/// Computes the space available for the arguments of a new process.
+#[cfg(unix)]
+pub fn arg_max(input: Result<usize, ()>) -> io::Result<Io> {
+    if input.is_ok() {
+        let kind = io::ErrorKind::InvalidInput;
+        return Err(io::Error::new(kind, "argument size limit already computed"));
+    }
+
+    Ok(Io::ArgMax(Ok(super::unix::arg_max())))
+}
+
+/// Spawns a process in a new pseudo-terminal.
+///
+/// This function allocates a pseudo-terminal pair, builds a
+/// [`tokio::process::Command`] from the flow's command builder
+/// then spawns a process in a new session whose controlling terminal
+/// is the slave side.
+///
+/// The master side of the returned pseudo-terminal is non-blocking,
+/// and should be read and written through a [`PtyMaster`].
+#[cfg(target_os = "linux")]
+pub fn spawn_pty(input: Result<Pty, (Command, WindowSize)>) -> io::Result<Io> {
+    let Err((mut command, size)) = input else {
+        let kind = io::ErrorKind::InvalidInput;
+        return Err(io::Error::new(kind, "missing command"));
+    };
+
+    shared::check_current_dir(&command)?;
+    let map_err = shared::map_spawn_error(&command);
+
+    // the child process already leads a new session
+    command.setsid = false;
+    command.process_group = None;
+
+    #[cfg(feature = "tracing")]
+    let span = trace::span(&command);
+
+    let kill_on_drop = command.kill_on_drop;
+    let command = TokioCommand::from(command).into_std();
+    let mut pty = super::unix::spawn_pty(command, size, map_err)?;
+    super::unix::set_nonblocking(pty.master.as_raw_fd())?;
+    pty.child.kill_on_drop = kill_on_drop;
+
+    #[cfg(feature = "tracing")]
+    let pty = Pty {
+        child: trace::attach(pty.child, span),
+        ..pty
+    };
+
+    Ok(Io::SpawnPty(Ok(pty)))
+}
+
+/// Resizes the window of a pseudo-terminal.
+#[cfg(target_os = "linux")]
+pub fn resize_pty(input: Result<Pty, (Pty, WindowSize)>) -> io::Result<Io> {
+    let Err((pty, size)) = input else {
+        let kind = io::ErrorKind::InvalidInput;
+        return Err(io::Error::new(kind, "missing pty"));
+    };
+
+    super::unix::set_window_size(&pty.master, size)?;
+
+    Ok(Io::ResizePty(Ok(pty)))
+}
+
+/// The master side of a pseudo-terminal, readable and writable
+/// asynchronously.
+///
+/// Reading returns what the child process writes to its terminal,
+/// writing simulates terminal input. Once the child process exits,
+/// reading fails with `EIO`.
+#[cfg(target_os = "linux")]
+#[derive(Debug)]
+pub struct PtyMaster {
+    fd: AsyncFd<File>,
+}
+
+#[cfg(target_os = "linux")]
+impl PtyMaster {
+    /// Creates a new asynchronous master from the given
+    /// pseudo-terminal, spawned by [`spawn_pty`].
+    ///
+    /// The master side is duplicated, so that the pseudo-terminal can
+    /// still be resized.
+    pub fn new(pty: &Pty) -> io::Result<Self> {
+        let master = pty.master.try_clone()?;
+        super::unix::set_nonblocking(master.as_raw_fd())?;
+        let fd = AsyncFd::new(master)?;
+        Ok(Self { fd })
+    }
+}
+
+#[cfg(target_os = "linux")]
+impl AsyncRead for PtyMaster {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        loop {
+            let mut guard = ready!(self.fd.poll_read_ready(cx))?;
+
+            match guard.try_io(|fd| fd.get_ref().read(buf.initialize_unfilled())) {
+                Ok(Ok(n)) => {
+                    buf.advance(n);
+                    return Poll::Ready(Ok(()));
+                }
+                Ok(Err(err)) => return Poll::Ready(Err(err)),
+                Err(_would_block) => continue,
+            }
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+impl AsyncWrite for PtyMaster {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        loop {
+            let mut guard = ready!(self.fd.poll_write_ready(cx))?;
+
+            match guard.try_io(|fd| fd.get_ref().write(buf)) {
+                Ok(result) => return Poll::Ready(result),
+                Err(_would_block) => continue,
+            }
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+}
+
+/// Converts a [`Command`] builder to a [`tokio::process::Command`].
+///
+/// [`Command::kill_on_drop`] is forwarded to
+/// [`tokio::process::Command::kill_on_drop`], for child processes
+/// spawned through tokio directly.
 impl From<Command> for TokioCommand {
-    fn from(builder: Command) -> Self {
-        let mut command = TokioCommand::new(builder.program);
+    fn from(mut builder: Command) -> Self {
+        let mut command = TokioCommand::new(&builder.program);
+        command.kill_on_drop(builder.kill_on_drop);
+
+        #[cfg(unix)]
+        super::unix::configure(command.as_std_mut(), &mut builder);
 
         if let Some(args) = builder.args {
             for arg in args {
@@ -81,6 +783,11 @@ impl From<Command> for TokioCommand {
             }
         }
 
+        if let Some(vars) = shared::inherited_envs(&builder.env_inherit) {
+            command.env_clear();
+            command.envs(vars);
+        }
+
         if let Some(envs) = builder.envs {
             for (key, val) in envs {
                 command.env(key, val);
@@ -106,3 +813,305 @@ impl From<Command> for TokioCommand {
         command
     }
 }
+
+#[cfg(all(test, unix))]
+mod tests {
+    use std::{process::Stdio, time::Duration};
+
+    use crate::{
+        coroutines::{
+            Expect, ExpectOutput, ExpectStep, JobPool, RestartPolicy, Spawn, SpawnDetached,
+            SpawnThenCapture, SpawnThenWait, SpawnThenWaitWithOutput, Supervise, SuperviseEvent,
+            Wait,
+        },
+        Capture, Command, Io, OutputLimit, RlimitResource,
+    };
+
+    use super::handle;
+
+    /// Drives the given coroutine resume function to completion,
+    /// handling each requested I/O with the Tokio runtime.
+    async fn run<T>(
+        mut resume: impl FnMut(Option<crate::Io>) -> Result<T, crate::Io>,
+    ) -> std::io::Result<T> {
+        let mut arg = None;
+
+        loop {
+            match resume(arg.take()) {
+                Ok(output) => break Ok(output),
+                Err(io) => arg = Some(handle(io).await?),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn kill_on_drop() {
+        let mut command = Command::new("sleep");
+        command.arg("60").kill_on_drop(true);
+
+        let child = tokio::process::Command::from(command).spawn().unwrap();
+        let pid = child.id().unwrap() as libc::pid_t;
+        drop(child);
+
+        // the child process is killed, then reaped by tokio
+        for _ in 0..100 {
+            if unsafe { libc::kill(pid, 0) } == -1 {
+                break;
+            }
+
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+
+        let err = std::io::Error::last_os_error;
+        assert_eq!(-1, unsafe { libc::kill(pid, 0) });
+        assert_eq!(Some(libc::ESRCH), err().raw_os_error());
+    }
+
+    #[tokio::test]
+    async fn rlimit_open_files() {
+        let mut command = Command::new("sh");
+        command
+            .arg("-c")
+            .arg("exec 3</dev/null 4</dev/null 5</dev/null");
+        command.stderr(Stdio::null());
+        command.rlimit(RlimitResource::OpenFiles, Some(4), Some(4));
+
+        let mut spawn = SpawnThenWait::new(command);
+        let output = run(|io| spawn.resume(io)).await.unwrap();
+
+        assert!(!output.status.success());
+    }
+
+    #[tokio::test]
+    async fn expect_script() {
+        let mut command = Command::new("sh");
+        command
+            .arg("-c")
+            .arg("printf 'Name: '; read n; echo \"hello $n\"");
+        command.stdin(Stdio::piped());
+        command.stdout(Stdio::piped());
+
+        let mut spawn = Spawn::new(command);
+        let child = run(|io| spawn.resume(io)).await.unwrap();
+
+        let timeout = Duration::from_secs(5);
+        let steps = [
+            ExpectStep::new("Name: ", "world\n").timeout(timeout),
+            ExpectStep::new("hello world", "").timeout(timeout),
+        ];
+
+        let mut expect = Expect::new(child, steps);
+        let child = match run(|io| expect.resume(io)).await.unwrap() {
+            ExpectOutput::Done(child) => child,
+            output => panic!("unexpected output: {output:?}"),
+        };
+
+        let mut wait = Wait::new(child);
+        let output = run(|io| wait.resume(io)).await.unwrap();
+
+        assert!(output.status.success());
+    }
+
+    #[tokio::test]
+    async fn job_pool() {
+        let commands = (0..10).map(|i| {
+            let mut command = Command::new("echo");
+            command.arg(i.to_string());
+            command
+        });
+
+        let mut pool = JobPool::new(commands, 4);
+        let mut outputs = vec![None; 10];
+
+        while let Some((index, output)) = run(|io| pool.resume(io)).await.unwrap() {
+            outputs[index] = Some(output.stdout);
+        }
+
+        for (i, output) in outputs.into_iter().enumerate() {
+            assert_eq!(Some(format!("{i}\n").into_bytes()), output);
+        }
+    }
+
+    #[tokio::test]
+    async fn usage() {
+        let mut command = Command::new("sh");
+        command.arg("-c").arg("sleep 0.2");
+
+        let mut spawn = SpawnThenWait::new(command);
+        let output = run(|io| spawn.resume(io)).await.unwrap();
+
+        assert!(output.status.success());
+        assert!(output.usage.duration >= Duration::from_millis(200));
+        #[cfg(target_os = "linux")]
+        assert!(output.usage.max_rss.unwrap() > 0);
+
+        let mut command = Command::new("sh");
+        command.arg("-c").arg("sleep 0.2; echo done");
+
+        let mut spawn = SpawnThenWaitWithOutput::new(command);
+        let output = run(|io| spawn.resume(io)).await.unwrap();
+
+        assert_eq!(b"done\n", output.stdout.as_slice());
+        assert!(output.usage.duration >= Duration::from_millis(200));
+        #[cfg(target_os = "linux")]
+        assert!(output.usage.max_rss.unwrap() > 0);
+    }
+
+    #[tokio::test]
+    async fn wait_timeout() {
+        let mut command = Command::new("sh");
+        command.arg("-c").arg("sleep 0.3");
+
+        #[allow(unused_mut)]
+        let Ok(Io::Spawn(Ok(mut child))) = handle(Io::Spawn(Err(command))).await
+        else {
+            panic!("child should be spawned");
+        };
+
+        // the exit is then checked periodically instead of being
+        // awaited
+        #[cfg(target_os = "linux")]
+        {
+            child.pidfd = None;
+        }
+
+        let io = Io::WaitTimeout(Err((child, Duration::from_millis(50))));
+        let Ok(Io::WaitTimeout(Ok(Err(child)))) = handle(io).await else {
+            panic!("child should still be running");
+        };
+
+        let io = Io::WaitTimeout(Err((child, Duration::from_secs(5))));
+        let Ok(Io::WaitTimeout(Ok(Ok(output)))) = handle(io).await else {
+            panic!("child should have exited");
+        };
+
+        assert!(output.status.success());
+        assert!(output.usage.duration >= Duration::from_millis(300));
+    }
+
+    #[tokio::test]
+    async fn supervise() {
+        let command = || {
+            let mut command = Command::new("sh");
+            command.arg("-c").arg("exit 1");
+            command
+        };
+
+        let millis = Duration::from_millis;
+        let policy = RestartPolicy::default()
+            .max_restarts(2, Duration::from_secs(60))
+            .backoff(millis(10), millis(15), 2.0);
+
+        let mut supervise = Supervise::new(command, policy);
+        let mut delays = Vec::new();
+        let mut gave_up = false;
+
+        while let Some(event) = run(|io| supervise.resume(io)).await.unwrap() {
+            match event {
+                SuperviseEvent::Restarting(delay) => delays.push(delay),
+                SuperviseEvent::GaveUp => gave_up = true,
+                _ => (),
+            }
+        }
+
+        // the second delay saturates to the maximum delay
+        assert_eq!(vec![millis(10), millis(15)], delays);
+        assert!(gave_up);
+    }
+
+    #[tokio::test]
+    async fn spawn_then_capture() {
+        let mut command = Command::new("sh");
+        command.arg("-c").arg("seq 1 100000");
+        let capture = Capture::new().limit(OutputLimit::keep_tail(7));
+
+        let mut spawn = SpawnThenCapture::new(command, capture);
+        let output = run(|io| spawn.resume(io)).await.unwrap();
+
+        assert!(output.status.success());
+        assert_eq!(b"100000\n", output.stdout.bytes.as_slice());
+        assert!(output.stdout.is_truncated());
+    }
+
+    #[tokio::test]
+    async fn spawn_then_wait_with_output_limit() {
+        let mut command = Command::new("sh");
+        command.arg("-c").arg("seq 1 100000; echo error >&2");
+        command.stdout(Stdio::piped());
+
+        let mut spawn = SpawnThenWaitWithOutput::new(command)
+            .stdout_limit(OutputLimit::keep_tail(7))
+            .stderr_limit(OutputLimit::keep_head(3));
+        let output = run(|io| spawn.resume(io)).await.unwrap();
+
+        assert!(output.status.success());
+        assert!(output.is_truncated());
+        assert_eq!(b"100000\n", output.stdout.as_slice());
+        assert_eq!(588_888, output.stdout_discarded);
+        assert_eq!(b"err", output.stderr.as_slice());
+        assert_eq!(3, output.stderr_discarded);
+
+        let mut command = Command::new("yes");
+        command.stdout(Stdio::piped());
+
+        let mut spawn = SpawnThenWaitWithOutput::new(command).limit(OutputLimit::error(1024));
+        let io = spawn.resume(None).unwrap_err();
+        let err = handle(io).await.unwrap_err();
+        assert_eq!(std::io::ErrorKind::Other, err.kind());
+    }
+
+    #[tokio::test]
+    async fn spawn_detached() {
+        let dir = tempdir::TempDir::new("spawn-detached").unwrap();
+        let path = dir.path().join("pid");
+
+        let mut command = Command::new("sh");
+        command.arg("-c").arg("echo $$ > pid.tmp; mv pid.tmp pid");
+        command.current_dir(dir.path());
+
+        let mut spawn = SpawnDetached::new(command);
+        let pid = run(|io| spawn.resume(io)).await.unwrap();
+
+        for _ in 0..100 {
+            if path.exists() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+
+        let content = std::fs::read_to_string(path).unwrap();
+        assert_eq!(format!("{pid}\n"), content);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn spawn_pty() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        use crate::{coroutines::SpawnPty, WindowSize};
+
+        use super::PtyMaster;
+
+        let mut command = Command::new("sh");
+        command.arg("-c").arg("test -t 0 && read line && stty size");
+
+        let mut spawn = SpawnPty::new(command, WindowSize::new(30, 100));
+        let pty = run(|io| spawn.resume(io)).await.unwrap();
+
+        let mut master = PtyMaster::new(&pty).unwrap();
+        master.write_all(b"go\n").await.unwrap();
+
+        // reading from the master fails with EIO once the child exits
+        let mut output = Vec::new();
+        let mut buf = [0; 64];
+        while let Ok(n @ 1..) = master.read(&mut buf).await {
+            output.extend_from_slice(&buf[..n]);
+        }
+
+        let mut wait = Wait::new(pty.child);
+        let status = run(|io| wait.resume(io)).await.unwrap().status;
+
+        assert!(status.success());
+        assert_eq!(b"go\r\n30 100\r\n", output.as_slice());
+    }
+}
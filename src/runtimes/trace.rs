@@ -0,0 +1,68 @@
+//! Module dedicated to [`tracing`] spans and events, shared by
+//! runtimes.
+//!
+//! Each spawned process gets its own span, holding the program, its
+//! redacted arguments and its working directory. The process ID, the
+//! exit status and the duration are recorded as they become known,
+//! and runtime events are emitted inside the span.
+
+#[cfg(unix)]
+use std::time::Duration;
+use std::{process::ExitStatus, time::Instant};
+
+use tracing::{debug, field, info, Span};
+
+#[cfg(unix)]
+use crate::Signal;
+use crate::{Child, Command};
+
+/// Creates the span of a process about to be spawned from the given
+/// command.
+pub(crate) fn span(command: &Command) -> Span {
+    tracing::info_span!(
+        "process",
+        program = ?command.program,
+        args = ?command.get_redacted_args(),
+        cwd = ?command.current_dir,
+        pid = field::Empty,
+        status = field::Empty,
+        duration_ms = field::Empty,
+    )
+}
+
+/// Records the ID of a spawned process.
+pub(crate) fn spawned(span: &Span, pid: Option<u32>) {
+    if let Some(pid) = pid {
+        span.record("pid", pid);
+    }
+
+    span.in_scope(|| info!(pid, "spawned process"));
+}
+
+/// Records the ID of a spawned child process, then attaches the span
+/// to it.
+pub(crate) fn attach(mut child: Child, span: Span) -> Child {
+    spawned(&span, Some(child.id()));
+    child.span = span;
+    child
+}
+
+/// Records the exit status and the duration of a process.
+pub(crate) fn exited(span: &Span, status: &ExitStatus, started: Instant) {
+    let duration_ms = started.elapsed().as_millis() as u64;
+    span.record("status", field::display(status));
+    span.record("duration_ms", duration_ms);
+    span.in_scope(|| info!(%status, duration_ms, "process exited"));
+}
+
+/// Emits the event of a signal sent to a process.
+#[cfg(unix)]
+pub(crate) fn killed(span: &Span, signal: Signal) {
+    span.in_scope(|| info!(%signal, "sent signal to process"));
+}
+
+/// Emits the event of a read from a process that timed out.
+#[cfg(unix)]
+pub(crate) fn timed_out(span: &Span, timeout: Option<Duration>) {
+    span.in_scope(|| debug!(?timeout, "read from process timed out"));
+}
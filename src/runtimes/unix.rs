@@ -0,0 +1,871 @@
+//! Module dedicated to Unix-specific process options, shared by
+//! runtimes.
+
+use std::{
+    collections::BTreeMap,
+    env,
+    ffi::CString,
+    io::{self, Read, Write},
+    mem,
+    os::{
+        fd::{AsRawFd, FromRawFd, OwnedFd, RawFd},
+        unix::{ffi::OsStringExt, process::CommandExt},
+    },
+    path::Path,
+    process::{Command as StdCommand, ExitStatus},
+    time::{Duration, Instant},
+};
+
+#[cfg(feature = "std")]
+use std::thread;
+
+#[cfg(target_os = "linux")]
+use std::{
+    ffi::{CStr, OsStr},
+    fs::{File, OpenOptions},
+    os::unix::{ffi::OsStrExt, fs::OpenOptionsExt},
+    process::Child as StdChild,
+};
+
+use crate::{Child, Chunk, Command, Job, ReadOutput, Rlimit, RlimitResource, Signal, Stream};
+#[cfg(target_os = "linux")]
+use crate::{Pty, Usage, WindowSize};
+
+/// Applies Unix-specific options of the given [`Command`] builder to
+/// the given [`std::process::Command`].
+///
+/// Runtimes based on other commands can still use this function
+/// through their standard command reference, like
+/// [`tokio::process::Command::as_std_mut`].
+pub(crate) fn configure(command: &mut StdCommand, builder: &mut Command) {
+    if let Some(arg0) = builder.arg0.take() {
+        command.arg0(arg0);
+    }
+
+    // Resource limits are applied first, so that the child process
+    // cannot raise them again once its privileges dropped.
+    if let Some(rlimits) = builder.rlimits.take() {
+        // SAFETY: the hook only performs async-signal-safe calls
+        unsafe { command.pre_exec(move || rlimits.iter().try_for_each(set_rlimit)) };
+    }
+
+    // The standard library switches user and group before running
+    // pre-exec hooks, which would then lack privileges to set
+    // resource limits, and only sets supplementary groups on nightly.
+    // Credentials are therefore all set manually, in the right order.
+    //
+    // The standard library also enters the working directory before
+    // running pre-exec hooks, with the privileges of the parent. It is
+    // entered again once privileges dropped, so that a directory the
+    // target user cannot access still makes the spawn fail.
+    if builder.uid.is_some() || builder.gid.is_some() || builder.groups.is_some() {
+        let uid = builder.uid;
+        let gid = builder.gid;
+        let groups = builder.groups.take();
+        let dir = builder.current_dir.as_deref().and_then(absolute_path);
+        // SAFETY: the hook only performs async-signal-safe calls
+        unsafe {
+            command.pre_exec(move || {
+                set_credentials(uid, gid, groups.as_deref())?;
+
+                if let Some(dir) = &dir {
+                    cvt(libc::chdir(dir.as_ptr()))?;
+                }
+
+                Ok(())
+            })
+        };
+    }
+
+    if builder.setsid {
+        // SAFETY: the hook only performs async-signal-safe calls
+        unsafe { command.pre_exec(|| cvt(libc::setsid()).map(drop)) };
+    } else if let Some(pgroup) = builder.process_group {
+        command.process_group(pgroup);
+    }
+
+    if let Some(mask) = builder.umask {
+        // SAFETY: the hook only performs async-signal-safe calls
+        unsafe {
+            command.pre_exec(move || {
+                libc::umask(mask as libc::mode_t);
+                Ok(())
+            })
+        };
+    }
+
+    if let Some(fds) = builder.fds.take() {
+        // target numbers are reserved until the command is dropped,
+        // so that the standard library cannot open the pipe
+        // reporting exec errors at one of them while spawning
+        let reserved = reserve_fds(&fds);
+        // temporary descriptors are allocated upfront, since the hook
+        // cannot allocate memory
+        let mut tmp_fds = Vec::with_capacity(fds.len());
+        // SAFETY: the hook only performs async-signal-safe calls,
+        // and the source descriptors live as long as the command
+        unsafe {
+            command.pre_exec(move || {
+                // reservations live as long as the hook
+                let _ = &reserved;
+                inherit_fds(&fds, &mut tmp_fds)
+            })
+        };
+    }
+
+    // The parent death signal is reset when credentials change, so
+    // it has to be set after them.
+    #[cfg(target_os = "linux")]
+    if let Some(signal) = builder.parent_death_signal {
+        let parent = std::process::id() as libc::pid_t;
+        // SAFETY: the hook only performs async-signal-safe calls
+        unsafe { command.pre_exec(move || set_parent_death_signal(signal, parent)) };
+    }
+}
+
+/// Sets the signal received by the current process when the given
+/// parent exits.
+///
+/// The signal is raised straight away if the parent already exited
+/// before the call, since it would never be sent otherwise.
+#[cfg(target_os = "linux")]
+fn set_parent_death_signal(signal: Signal, parent: libc::pid_t) -> io::Result<()> {
+    cvt(unsafe { libc::prctl(libc::PR_SET_PDEATHSIG, signal.0 as libc::c_ulong) })?;
+
+    if unsafe { libc::getppid() } != parent {
+        cvt(unsafe { libc::raise(signal.0) })?;
+    }
+
+    Ok(())
+}
+
+/// Sets the given resource limit of the current process.
+///
+/// Bounds set to `None` keep their current value, the soft limit
+/// being lowered to the hard one if needed.
+fn set_rlimit(rlimit: &Rlimit) -> io::Result<()> {
+    let resource = match rlimit.resource {
+        RlimitResource::AddressSpace => libc::RLIMIT_AS,
+        RlimitResource::Core => libc::RLIMIT_CORE,
+        RlimitResource::Cpu => libc::RLIMIT_CPU,
+        RlimitResource::Data => libc::RLIMIT_DATA,
+        RlimitResource::FileSize => libc::RLIMIT_FSIZE,
+        RlimitResource::OpenFiles => libc::RLIMIT_NOFILE,
+        RlimitResource::Processes => libc::RLIMIT_NPROC,
+        RlimitResource::Stack => libc::RLIMIT_STACK,
+    };
+
+    let limit = |limit: u64| match limit {
+        u64::MAX => libc::RLIM_INFINITY,
+        limit => limit as libc::rlim_t,
+    };
+
+    let mut current = libc::rlimit {
+        rlim_cur: 0,
+        rlim_max: 0,
+    };
+
+    cvt(unsafe { libc::getrlimit(resource, &mut current) })?;
+
+    let hard = rlimit.hard.map_or(current.rlim_max, limit);
+    let soft = rlimit.soft.map_or(current.rlim_cur.min(hard), limit);
+
+    let rlimit = libc::rlimit {
+        rlim_cur: soft,
+        rlim_max: hard,
+    };
+
+    cvt(unsafe { libc::setrlimit(resource, &rlimit) })?;
+    Ok(())
+}
+
+/// Reserves the target numbers of the given descriptors that are
+/// free in the current process, by duplicating a source to them.
+///
+/// Descriptors opened meanwhile, like the close-on-exec pipe the
+/// standard library uses to report exec errors, cannot be allocated
+/// to a reserved number, and are therefore not overridden by
+/// [`inherit_fds`]. Targets already open in the current process are
+/// left as is: the descriptors opened while spawning cannot be
+/// allocated to them either, unless closed by another thread
+/// meanwhile.
+fn reserve_fds(fds: &BTreeMap<RawFd, OwnedFd>) -> Vec<OwnedFd> {
+    let Some(source) = fds.values().next() else {
+        return Vec::new();
+    };
+
+    let mut reserved = Vec::new();
+
+    for &child_fd in fds.keys() {
+        // SAFETY: the source descriptor is valid
+        let fd = unsafe { libc::fcntl(source.as_raw_fd(), libc::F_DUPFD_CLOEXEC, child_fd) };
+
+        if fd < 0 {
+            continue;
+        }
+
+        // SAFETY: the descriptor has just been opened, with close-on-exec
+        let fd = unsafe { OwnedFd::from_raw_fd(fd) };
+
+        // the lowest free descriptor above the target is released
+        if fd.as_raw_fd() == child_fd {
+            reserved.push(fd);
+        }
+    }
+
+    reserved
+}
+
+/// Duplicates source descriptors to their number in the child
+/// process.
+///
+/// Sources are first duplicated to the lowest free descriptors above
+/// all target numbers, so that a source cannot be overridden by
+/// another mapping before being duplicated itself.
+///
+/// Targets then override whatever descriptor is open at their
+/// number, which is why they are reserved before spawning (see
+/// [`reserve_fds`]).
+fn inherit_fds(fds: &BTreeMap<RawFd, OwnedFd>, tmp_fds: &mut Vec<RawFd>) -> io::Result<()> {
+    let base = fds.keys().copied().max().unwrap_or(2) + 1;
+    tmp_fds.clear();
+
+    for source in fds.values() {
+        let tmp_fd = unsafe { libc::fcntl(source.as_raw_fd(), libc::F_DUPFD_CLOEXEC, base) };
+        tmp_fds.push(cvt(tmp_fd)?);
+    }
+
+    for (&tmp_fd, &child_fd) in tmp_fds.iter().zip(fds.keys()) {
+        cvt(unsafe { libc::dup2(tmp_fd, child_fd) })?;
+        cvt(unsafe { libc::close(tmp_fd) })?;
+    }
+
+    Ok(())
+}
+
+/// Sends the given signal to the given child process.
+///
+/// If the child leads a process group, the signal is sent to the
+/// whole group instead.
+///
+/// On Linux, the signal is sent through the process file descriptor
+/// of the child when available, so that it cannot reach another
+/// process reusing the child's ID.
+pub(crate) fn kill(child: &Child, signal: Signal) -> io::Result<()> {
+    #[cfg(target_os = "linux")]
+    if let (None, Some(pidfd)) = (child.group, &child.pidfd) {
+        return pidfd_send_signal(pidfd, signal);
+    }
+
+    let pid = match child.group {
+        Some(pgid) => -(pgid as libc::pid_t),
+        None => child.id() as libc::pid_t,
+    };
+
+    cvt(unsafe { libc::kill(pid, signal.0) })?;
+    Ok(())
+}
+
+/// Opens a process file descriptor referring to the given process
+/// using `pidfd_open(2)`.
+///
+/// Returns [`None`] when the kernel does not support it (before
+/// Linux 5.3) or when the call is denied, in which case runtimes
+/// fall back to process IDs.
+#[cfg(target_os = "linux")]
+pub(crate) fn pidfd_open(pid: u32) -> Option<OwnedFd> {
+    // SAFETY: the syscall takes no pointer
+    let fd = unsafe { libc::syscall(libc::SYS_pidfd_open, pid as libc::pid_t, 0) };
+
+    if fd < 0 {
+        return None;
+    }
+
+    // SAFETY: the descriptor has just been opened, with close-on-exec
+    Some(unsafe { OwnedFd::from_raw_fd(fd as RawFd) })
+}
+
+#[cfg(target_os = "linux")]
+fn pidfd_send_signal(pidfd: &OwnedFd, signal: Signal) -> io::Result<()> {
+    let fd = pidfd.as_raw_fd();
+    let null = std::ptr::null::<libc::siginfo_t>();
+    // SAFETY: a null siginfo is allowed, the descriptor is valid
+    let code = unsafe { libc::syscall(libc::SYS_pidfd_send_signal, fd, signal.0, null, 0) };
+    cvt(code as libc::c_int)?;
+    Ok(())
+}
+
+/// Waits for the given child process to exit, for at most the given
+/// duration, without reaping it.
+///
+/// Returns `true` if the child process exited. On Linux, the process
+/// file descriptor of the child is polled when available, otherwise
+/// the child process is checked periodically.
+#[cfg(feature = "std")]
+pub(crate) fn wait_timeout(child: &Child, timeout: Duration) -> io::Result<bool> {
+    let deadline = Instant::now() + timeout;
+
+    #[cfg(target_os = "linux")]
+    if let Some(pidfd) = &child.pidfd {
+        return poll_pidfd(pidfd, deadline);
+    }
+
+    loop {
+        if exited(child.id())? {
+            return Ok(true);
+        }
+
+        let now = Instant::now();
+
+        if now >= deadline {
+            return Ok(false);
+        }
+
+        thread::sleep((deadline - now).min(Duration::from_millis(10)));
+    }
+}
+
+/// Returns `true` if the given child process exited, without reaping
+/// it.
+///
+/// A child process already reaped is considered exited.
+pub(crate) fn exited(pid: u32) -> io::Result<bool> {
+    let flags = libc::WEXITED | libc::WNOHANG | libc::WNOWAIT;
+    // SAFETY: siginfo is a plain C struct, valid when zeroed
+    let mut info: libc::siginfo_t = unsafe { mem::zeroed() };
+
+    // SAFETY: the pointer is valid for the whole call
+    while unsafe { libc::waitid(libc::P_PID, pid as libc::id_t, &mut info, flags) } == -1 {
+        let err = io::Error::last_os_error();
+
+        match err.raw_os_error() {
+            Some(libc::EINTR) => continue,
+            Some(libc::ECHILD) => return Ok(true),
+            _ => return Err(err),
+        }
+    }
+
+    // the process ID stays zero while the child process is running
+    Ok(unsafe { info.si_pid() } != 0)
+}
+
+/// Polls the given process file descriptor until its child process
+/// exits or the given deadline is reached.
+#[cfg(all(feature = "std", target_os = "linux"))]
+fn poll_pidfd(pidfd: &OwnedFd, deadline: Instant) -> io::Result<bool> {
+    loop {
+        let mut fd = libc::pollfd {
+            fd: pidfd.as_raw_fd(),
+            events: libc::POLLIN,
+            revents: 0,
+        };
+
+        let timeout = deadline.saturating_duration_since(Instant::now());
+        let timeout = timeout.as_millis().min(libc::c_int::MAX as u128) as libc::c_int;
+
+        match cvt(unsafe { libc::poll(&mut fd, 1, timeout) }) {
+            Ok(n) => return Ok(n > 0),
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+            Err(err) => return Err(err),
+        }
+    }
+}
+
+/// Spawns the given command detached, then returns the ID of the
+/// detached process.
+///
+/// The child process starts a new session then forks again, so that
+/// the grandchild is not a session leader and cannot acquire a
+/// controlling terminal. The child process sends the grandchild ID
+/// through a pipe then exits straight away, and is reaped before
+/// returning. The grandchild is then reparented to init, or to the
+/// closest subreaper.
+///
+/// Errors from the grandchild (like a missing program) are still
+/// reported by the standard library, which waits for the exec.
+pub(crate) fn spawn_detached(mut command: StdCommand) -> io::Result<u32> {
+    let (mut reader, writer) = io::pipe()?;
+    let fd = writer.as_raw_fd();
+
+    // SAFETY: the hook only performs async-signal-safe calls, and the
+    // writer lives as long as the command
+    unsafe { command.pre_exec(move || detach(fd)) };
+
+    let mut child = command.spawn()?;
+    drop(command);
+    drop(writer);
+    let status = child.wait()?;
+
+    let mut pid = Vec::with_capacity(mem::size_of::<libc::pid_t>());
+    reader.read_to_end(&mut pid)?;
+
+    match <[u8; mem::size_of::<libc::pid_t>()]>::try_from(pid) {
+        Ok(pid) if status.success() => Ok(libc::pid_t::from_ne_bytes(pid) as u32),
+        _ => Err(io::Error::other(format!(
+            "cannot receive the ID of the detached process ({status})"
+        ))),
+    }
+}
+
+/// Starts a new session then forks, so that the parent process exits
+/// after sending the child ID to the given pipe.
+fn detach(fd: RawFd) -> io::Result<()> {
+    cvt(unsafe { libc::setsid() })?;
+
+    let pid = match unsafe { libc::fork() } {
+        -1 => return Err(io::Error::last_os_error()),
+        0 => return Ok(()),
+        pid => pid,
+    };
+
+    let bytes = pid.to_ne_bytes();
+    // SAFETY: the buffer is valid for the whole call
+    let n = unsafe { libc::write(fd, bytes.as_ptr().cast(), bytes.len()) };
+
+    // the grandchild cannot be reported, so it must not outlive the
+    // child process
+    if n != bytes.len() as libc::ssize_t {
+        unsafe {
+            libc::kill(pid, libc::SIGKILL);
+            libc::_exit(1)
+        }
+    }
+
+    unsafe { libc::_exit(0) }
+}
+
+fn set_credentials(uid: Option<u32>, gid: Option<u32>, groups: Option<&[u32]>) -> io::Result<()> {
+    if let Some(groups) = groups {
+        cvt(unsafe { libc::setgroups(groups.len() as _, groups.as_ptr()) })?;
+    } else if uid.is_some() {
+        // Like the standard library, supplementary groups are
+        // dropped when switching user, unless privileges are
+        // missing to do so.
+        if let Err(err) = cvt(unsafe { libc::setgroups(0, std::ptr::null()) }) {
+            if err.raw_os_error() != Some(libc::EPERM) {
+                return Err(err);
+            }
+        }
+    }
+
+    if let Some(id) = gid {
+        cvt(unsafe { libc::setgid(id) })?;
+    }
+
+    if let Some(id) = uid {
+        cvt(unsafe { libc::setuid(id) })?;
+    }
+
+    Ok(())
+}
+
+/// Returns the given path as an absolute C string, relative paths
+/// being resolved against the current working directory.
+///
+/// Returns `None` if the path contains a nul byte, in which case
+/// spawning fails anyway.
+fn absolute_path(path: &Path) -> Option<CString> {
+    let path = match path.is_absolute() {
+        true => path.to_owned(),
+        false => env::current_dir().ok()?.join(path),
+    };
+
+    CString::new(path.into_os_string().into_vec()).ok()
+}
+
+/// Reads bytes from either the stdout or the stderr of the given
+/// child process.
+///
+/// Streams reaching the end of file are closed. If both streams are
+/// closed (or were not piped), [`ReadOutput::Eof`] is returned.
+pub(crate) fn read(child: &mut Child, timeout: Option<Duration>) -> io::Result<ReadOutput> {
+    let deadline = timeout.map(|timeout| Instant::now() + timeout);
+    let mut buf = [0; 8192];
+
+    loop {
+        let stdout = child.process.stdout.as_ref().map(AsRawFd::as_raw_fd);
+        let stderr = child.process.stderr.as_ref().map(AsRawFd::as_raw_fd);
+
+        let mut fds: Vec<_> = [stdout, stderr]
+            .into_iter()
+            .flatten()
+            .map(|fd| libc::pollfd {
+                fd,
+                events: libc::POLLIN,
+                revents: 0,
+            })
+            .collect();
+
+        if fds.is_empty() {
+            return Ok(ReadOutput::Eof);
+        }
+
+        let timeout = match deadline {
+            None => -1,
+            Some(deadline) => {
+                let timeout = deadline.saturating_duration_since(Instant::now());
+                timeout.as_millis().min(libc::c_int::MAX as u128) as libc::c_int
+            }
+        };
+
+        let n = unsafe { libc::poll(fds.as_mut_ptr(), fds.len() as libc::nfds_t, timeout) };
+
+        match cvt(n) {
+            Ok(0) => return Ok(ReadOutput::Timeout),
+            Ok(_) => (),
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+            Err(err) => return Err(err),
+        }
+
+        let Some(ready) = fds.iter().find(|fd| fd.revents != 0) else {
+            continue;
+        };
+
+        let (stream, n) = if Some(ready.fd) == stdout {
+            let Some(stdout) = child.process.stdout.as_mut() else {
+                continue;
+            };
+            (Stream::Stdout, stdout.read(&mut buf)?)
+        } else {
+            let Some(stderr) = child.process.stderr.as_mut() else {
+                continue;
+            };
+            (Stream::Stderr, stderr.read(&mut buf)?)
+        };
+
+        if n > 0 {
+            let bytes = buf[..n].to_vec();
+            return Ok(ReadOutput::Chunk(Chunk { stream, bytes }));
+        }
+
+        match stream {
+            Stream::Stdout => child.process.stdout = None,
+            Stream::Stderr => child.process.stderr = None,
+        }
+    }
+}
+
+/// Writes all the given bytes to the stdin of the given child
+/// process.
+pub(crate) fn write(child: &mut Child, bytes: &[u8]) -> io::Result<()> {
+    let Some(stdin) = child.process.stdin.as_mut() else {
+        let kind = io::ErrorKind::BrokenPipe;
+        return Err(io::Error::new(kind, "missing piped stdin"));
+    };
+
+    stdin.write_all(bytes)?;
+    stdin.flush()
+}
+
+/// Collects the output of the given jobs until one of them
+/// completes, then removes it from the given jobs.
+///
+/// A job completes once its output streams are closed and its child
+/// process exited, like [`std::process::Command::output`]. The child
+/// process is not reaped, so that its resource usage can be
+/// collected.
+#[cfg(feature = "std")]
+pub(crate) fn wait_any(jobs: &mut Vec<Job>) -> io::Result<Job> {
+    let mut buf = [0; 8192];
+
+    loop {
+        let mut exiting = false;
+
+        for i in 0..jobs.len() {
+            let process = &jobs[i].child.process;
+
+            if process.stdout.is_some() || process.stderr.is_some() {
+                continue;
+            }
+
+            if exited(process.id())? {
+                return Ok(jobs.remove(i));
+            }
+
+            exiting = true;
+        }
+
+        if jobs.is_empty() {
+            let kind = io::ErrorKind::InvalidInput;
+            return Err(io::Error::new(kind, "missing jobs"));
+        }
+
+        let mut streams = Vec::new();
+        let mut fds = Vec::new();
+        let mut exit_pollable = true;
+
+        for (i, job) in jobs.iter().enumerate() {
+            let stdout = job.child.process.stdout.as_ref().map(AsRawFd::as_raw_fd);
+            let stderr = job.child.process.stderr.as_ref().map(AsRawFd::as_raw_fd);
+
+            for (stream, fd) in [(Stream::Stdout, stdout), (Stream::Stderr, stderr)] {
+                if let Some(fd) = fd {
+                    streams.push((i, Some(stream)));
+                    fds.push(libc::pollfd {
+                        fd,
+                        events: libc::POLLIN,
+                        revents: 0,
+                    });
+                }
+            }
+
+            if stdout.is_some() || stderr.is_some() {
+                continue;
+            }
+
+            // process file descriptors become readable once their
+            // child process exits
+            #[cfg(target_os = "linux")]
+            if let Some(pidfd) = &job.child.pidfd {
+                streams.push((i, None));
+                fds.push(libc::pollfd {
+                    fd: pidfd.as_raw_fd(),
+                    events: libc::POLLIN,
+                    revents: 0,
+                });
+                continue;
+            }
+
+            exit_pollable = false;
+        }
+
+        // without process file descriptors, exit of child processes
+        // cannot be polled, so jobs with closed streams are checked
+        // periodically
+        let timeout = if exiting && !exit_pollable { 10 } else { -1 };
+        let n = unsafe { libc::poll(fds.as_mut_ptr(), fds.len() as libc::nfds_t, timeout) };
+
+        match cvt(n) {
+            Ok(_) => (),
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+            Err(err) => return Err(err),
+        }
+
+        for (fd, (i, stream)) in fds.iter().zip(streams) {
+            if fd.revents == 0 {
+                continue;
+            }
+
+            let job = &mut jobs[i];
+
+            match stream {
+                Some(Stream::Stdout) => {
+                    read_into(&mut job.child.process.stdout, &mut job.stdout, &mut buf)?
+                }
+                Some(Stream::Stderr) => {
+                    read_into(&mut job.child.process.stderr, &mut job.stderr, &mut buf)?
+                }
+                // exited jobs are collected at the next iteration
+                None => (),
+            }
+        }
+    }
+}
+
+/// Reads available bytes from the given pipe into the given output,
+/// and closes the pipe once it reaches the end of file.
+pub(crate) fn read_into<R: Read>(
+    pipe: &mut Option<R>,
+    output: &mut Vec<u8>,
+    buf: &mut [u8],
+) -> io::Result<()> {
+    let Some(reader) = pipe.as_mut() else {
+        return Ok(());
+    };
+
+    match reader.read(buf) {
+        Ok(0) => *pipe = None,
+        Ok(n) => output.extend_from_slice(&buf[..n]),
+        Err(err) if err.kind() == io::ErrorKind::Interrupted => (),
+        Err(err) if err.kind() == io::ErrorKind::WouldBlock => (),
+        Err(err) => return Err(err),
+    }
+
+    Ok(())
+}
+
+/// Kills then reaps the given jobs, so that they do not outlive a
+/// failed wait.
+pub(crate) fn abort_jobs(jobs: Vec<Job>) {
+    for mut job in jobs {
+        // errors mean that the child process already exited
+        let _ = kill(&job.child, Signal::KILL);
+        let _ = job.child.process.wait();
+    }
+}
+
+/// Spawns the given command in a new pseudo-terminal.
+///
+/// The given command should neither lead a new session nor a new
+/// process group, since its child process leads a new session with
+/// the pseudo-terminal as controlling terminal. The given mapper is
+/// only applied to spawn errors, not to pseudo-terminal ones.
+#[cfg(target_os = "linux")]
+pub(crate) fn spawn_pty(
+    mut command: StdCommand,
+    size: WindowSize,
+    map_err: impl FnOnce(io::Error) -> io::Error,
+) -> io::Result<Pty> {
+    let master = open_pty_master()?;
+    set_window_size(&master, size)?;
+    let slave = open_pty_slave(&master)?;
+
+    command.stdin(slave.try_clone()?);
+    command.stdout(slave.try_clone()?);
+    command.stderr(slave);
+
+    // SAFETY: the hook only performs async-signal-safe calls
+    unsafe {
+        command.pre_exec(|| {
+            cvt(libc::setsid())?;
+            cvt(libc::ioctl(libc::STDIN_FILENO, libc::TIOCSCTTY, 0))?;
+            Ok(())
+        })
+    };
+
+    let process = command.spawn().map_err(map_err)?;
+    let pidfd = pidfd_open(process.id());
+    let child = Child::new(process).with_group(Some(0)).with_pidfd(pidfd);
+
+    Ok(Pty { master, child })
+}
+
+/// Sets the window size of the given pseudo-terminal master.
+#[cfg(target_os = "linux")]
+pub(crate) fn set_window_size(master: &File, size: WindowSize) -> io::Result<()> {
+    let size = libc::winsize {
+        ws_row: size.rows,
+        ws_col: size.cols,
+        ws_xpixel: 0,
+        ws_ypixel: 0,
+    };
+
+    cvt(unsafe { libc::ioctl(master.as_raw_fd(), libc::TIOCSWINSZ, &size) })?;
+    Ok(())
+}
+
+#[cfg(target_os = "linux")]
+fn open_pty_master() -> io::Result<File> {
+    let flags = libc::O_RDWR | libc::O_NOCTTY | libc::O_CLOEXEC;
+    let fd = cvt(unsafe { libc::posix_openpt(flags) })?;
+    // SAFETY: the descriptor has just been opened
+    let master = unsafe { File::from_raw_fd(fd) };
+
+    cvt(unsafe { libc::grantpt(fd) })?;
+    cvt(unsafe { libc::unlockpt(fd) })?;
+
+    Ok(master)
+}
+
+#[cfg(target_os = "linux")]
+fn open_pty_slave(master: &File) -> io::Result<File> {
+    let mut name = [0; 64];
+    let code = unsafe { libc::ptsname_r(master.as_raw_fd(), name.as_mut_ptr(), name.len()) };
+
+    if code != 0 {
+        return Err(io::Error::from_raw_os_error(code));
+    }
+
+    // SAFETY: ptsname_r succeeded, the name is nul-terminated
+    let path = unsafe { CStr::from_ptr(name.as_ptr()) };
+    let path = OsStr::from_bytes(path.to_bytes());
+
+    OpenOptions::new()
+        .read(true)
+        .write(true)
+        .custom_flags(libc::O_NOCTTY)
+        .open(path)
+}
+
+/// Waits for the given child process to exit using a raw
+/// `waitid(2)` system call, which also reports its resource usage,
+/// then reaps it using the standard library.
+///
+/// The child process is left waitable (`WNOWAIT`), so that it is
+/// never reaped behind the back of the standard library, which would
+/// then send signals to a possibly reused process ID. Resource usage
+/// is not available when the child process has already been reaped.
+#[cfg(target_os = "linux")]
+pub(crate) fn wait(process: &mut StdChild) -> io::Result<(ExitStatus, Option<libc::rusage>)> {
+    let pid = process.id() as libc::id_t;
+    let flags = libc::WEXITED | libc::WNOWAIT;
+    // SAFETY: siginfo and rusage are plain C structs, valid when zeroed
+    let mut info: libc::siginfo_t = unsafe { mem::zeroed() };
+    let mut usage: libc::rusage = unsafe { mem::zeroed() };
+
+    // the libc wrapper does not expose the resource usage argument
+    let rusage = loop {
+        // SAFETY: pointers are valid for the whole call
+        let code = unsafe {
+            libc::syscall(
+                libc::SYS_waitid,
+                libc::P_PID,
+                pid,
+                &mut info as *mut libc::siginfo_t,
+                flags,
+                &mut usage as *mut libc::rusage,
+            )
+        };
+
+        if code != -1 {
+            break Some(usage);
+        }
+
+        let err = io::Error::last_os_error();
+
+        match err.raw_os_error() {
+            Some(libc::EINTR) => continue,
+            Some(libc::ECHILD) => break None,
+            _ => return Err(err),
+        }
+    };
+
+    Ok((process.wait()?, rusage))
+}
+
+/// Copies CPU times and maximum resident set size of the given
+/// resource usage to the given [`Usage`].
+#[cfg(target_os = "linux")]
+pub(crate) fn set_rusage(usage: &mut Usage, rusage: &libc::rusage) {
+    let time = |tv: libc::timeval| Duration::new(tv.tv_sec as u64, tv.tv_usec as u32 * 1000);
+    usage.user_time = Some(time(rusage.ru_utime));
+    usage.system_time = Some(time(rusage.ru_stime));
+    // Linux reports the maximum resident set size in kilobytes
+    usage.max_rss = Some(rusage.ru_maxrss as u64 * 1024);
+}
+
+/// Returns the space available for the arguments of a new process,
+/// in bytes.
+///
+/// Like `xargs`, some headroom is kept in addition to the space
+/// taken by the current environment.
+pub(crate) fn arg_max() -> usize {
+    // POSIX guarantees at least 4096 bytes
+    let max = match unsafe { libc::sysconf(libc::_SC_ARG_MAX) } {
+        max if max > 0 => max as usize,
+        _ => 4096,
+    };
+
+    let env: usize = env::vars_os()
+        .map(|(key, val)| key.len() + val.len() + 2 + mem::size_of::<usize>())
+        .sum();
+
+    max.saturating_sub(env).saturating_sub(2048)
+}
+
+/// Sets the `O_NONBLOCK` flag of the given file descriptor.
+#[cfg(feature = "tokio")]
+pub(crate) fn set_nonblocking(fd: RawFd) -> io::Result<()> {
+    let flags = cvt(unsafe { libc::fcntl(fd, libc::F_GETFL) })?;
+    cvt(unsafe { libc::fcntl(fd, libc::F_SETFL, flags | libc::O_NONBLOCK) })?;
+    Ok(())
+}
+
+/// Converts a libc return code into an [`io::Result`].
+fn cvt(code: libc::c_int) -> io::Result<libc::c_int> {
+    if code == -1 {
+        Err(io::Error::last_os_error())
+    } else {
+        Ok(code)
+    }
+}
@@ -1,18 +1,292 @@
 //! Module dedicated to [`serde`] de/serialization of [`Command`].
+//!
+//! A command holding nothing more than a program and its arguments
+//! is serialized as a list of strings. Any other option, including
+//! environment variables and the working directory, switches the
+//! output to the map form.
+//!
+//! Sensitive arguments and environment variables are serialized as
+//! [`Command::REDACTED`], along with their indexes and keys, so that
+//! a deserialized command keeps them marked as sensitive. The real
+//! values are lost: they must be set again before spawning.
 
-use std::fmt;
+use std::{
+    collections::{BTreeSet, HashMap},
+    fmt,
+    path::PathBuf,
+};
 
 use serde::{
-    de::{Error, SeqAccess, Visitor},
+    de::{value::MapAccessDeserializer, Error, MapAccess, SeqAccess, Visitor},
     ser::SerializeSeq,
     Deserialize, Deserializer, Serialize, Serializer,
 };
 
-use crate::Command;
+#[cfg(target_os = "linux")]
+use crate::Signal;
+use crate::{Command, EnvInherit, Redirect, Rlimit};
+
+/// The detailed representation of a [`Command`].
+///
+/// A command is serialized as a list of strings (the program followed
+/// by its arguments) unless it holds more options, in which case this
+/// representation is used instead.
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+struct CommandMap {
+    program: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    args: Option<Vec<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    arg0: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    envs: Option<HashMap<String, String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    sensitive_args: Option<BTreeSet<usize>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    sensitive_envs: Option<BTreeSet<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    env_inherit: Option<EnvInherit>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    current_dir: Option<PathBuf>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    umask: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    stdin: Option<Redirect>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    stdout: Option<Redirect>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    stderr: Option<Redirect>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    kill_on_drop: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    uid: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    gid: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    groups: Option<Vec<u32>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    process_group: Option<i32>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    setsid: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    rlimits: Option<Vec<Rlimit>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    parent_death_signal: Option<i32>,
+}
+
+impl CommandMap {
+    /// Returns `true` if the map holds nothing more than a program
+    /// and its arguments.
+    fn is_seq(&self) -> bool {
+        self.arg0.is_none()
+            && self.envs.is_none()
+            && self.sensitive_args.is_none()
+            && self.sensitive_envs.is_none()
+            && self.env_inherit.is_none()
+            && self.current_dir.is_none()
+            && self.umask.is_none()
+            && self.stdin.is_none()
+            && self.stdout.is_none()
+            && self.stderr.is_none()
+            && !self.kill_on_drop
+            && self.uid.is_none()
+            && self.gid.is_none()
+            && self.groups.is_none()
+            && self.process_group.is_none()
+            && !self.setsid
+            && self.rlimits.is_none()
+            && self.parent_death_signal.is_none()
+    }
+
+    /// Converts the map into a [`Command`].
+    fn into_command<E: Error>(self) -> Result<Command, E> {
+        if self.program.trim().is_empty() {
+            return Err(E::custom("command cannot be empty"));
+        }
+
+        let args = self.args.as_ref().map_or(0, Vec::len);
+
+        if matches!(&self.sensitive_args, Some(indexes) if indexes.iter().any(|i| *i >= args)) {
+            return Err(E::custom("sensitive argument index out of bounds"));
+        }
+
+        if let Some(keys) = &self.sensitive_envs {
+            let envs = self.envs.as_ref();
+
+            if keys
+                .iter()
+                .any(|key| !envs.is_some_and(|envs| envs.contains_key(key)))
+            {
+                return Err(E::custom("sensitive environment variable not set"));
+            }
+        }
+
+        let mut command = Command::new(self.program);
+
+        if let Some(args) = self.args {
+            command.args(args);
+        }
+
+        if let Some(envs) = self.envs {
+            command.envs(envs);
+        }
+
+        command.sensitive_args = self.sensitive_args;
+        command.sensitive_envs = self
+            .sensitive_envs
+            .map(|keys| keys.into_iter().map(Into::into).collect());
+
+        if let Some(policy) = self.env_inherit {
+            command.env_inherit(policy);
+        }
+
+        if let Some(dir) = self.current_dir {
+            command.current_dir(dir);
+        }
+
+        command.stdin_redirect = self.stdin;
+        command.stdout_redirect = self.stdout;
+        command.stderr_redirect = self.stderr;
+        command.kill_on_drop = self.kill_on_drop;
+
+        #[cfg(unix)]
+        {
+            command.arg0 = self.arg0.map(Into::into);
+            command.umask = self.umask;
+            command.uid = self.uid;
+            command.gid = self.gid;
+            command.groups = self.groups;
+            command.process_group = self.process_group;
+            command.setsid = self.setsid;
+            command.rlimits = self.rlimits;
+        }
+
+        #[cfg(target_os = "linux")]
+        {
+            command.parent_death_signal = self.parent_death_signal.map(Signal);
+        }
+
+        #[cfg(not(unix))]
+        if self.arg0.is_some() {
+            return Err(E::custom("arg0 is only supported on Unix"));
+        }
+
+        #[cfg(not(unix))]
+        if self.umask.is_some() {
+            return Err(E::custom("umask is only supported on Unix"));
+        }
+
+        #[cfg(not(unix))]
+        if self.uid.is_some() || self.gid.is_some() || self.groups.is_some() {
+            return Err(E::custom("credentials are only supported on Unix"));
+        }
+
+        #[cfg(not(unix))]
+        if self.process_group.is_some() || self.setsid {
+            return Err(E::custom("process groups are only supported on Unix"));
+        }
+
+        #[cfg(not(unix))]
+        if self.rlimits.is_some() {
+            return Err(E::custom("resource limits are only supported on Unix"));
+        }
 
+        #[cfg(not(target_os = "linux"))]
+        if self.parent_death_signal.is_some() {
+            return Err(E::custom("parent death signal is only supported on Linux"));
+        }
+
+        Ok(command)
+    }
+}
+
+impl From<&Command> for CommandMap {
+    fn from(command: &Command) -> Self {
+        let args = command.get_redacted_args().map(|args| {
+            args.iter()
+                .map(|arg| arg.to_string_lossy().into_owned())
+                .collect()
+        });
+
+        let envs = command.get_redacted_envs().map(|envs| {
+            envs.iter()
+                .map(|(key, val)| {
+                    let key = key.to_string_lossy().into_owned();
+                    let val = val.to_string_lossy().into_owned();
+                    (key, val)
+                })
+                .collect()
+        });
+
+        let sensitive_envs = command.sensitive_envs.as_ref().map(|keys| {
+            keys.iter()
+                .map(|key| key.to_string_lossy().into_owned())
+                .collect()
+        });
+
+        #[allow(unused_mut)]
+        let mut map = CommandMap {
+            program: command.program.to_string_lossy().into_owned(),
+            args,
+            envs,
+            sensitive_args: command.sensitive_args.clone(),
+            sensitive_envs,
+            env_inherit: match &command.env_inherit {
+                EnvInherit::All => None,
+                policy => Some(policy.clone()),
+            },
+            current_dir: command.current_dir.clone(),
+            stdin: command.stdin_redirect.clone(),
+            stdout: command.stdout_redirect.clone(),
+            stderr: command.stderr_redirect.clone(),
+            kill_on_drop: command.kill_on_drop,
+            ..Default::default()
+        };
+
+        #[cfg(unix)]
+        {
+            map.arg0 = command
+                .arg0
+                .as_ref()
+                .map(|arg0| arg0.to_string_lossy().into_owned());
+            map.umask = command.umask;
+            map.uid = command.uid;
+            map.gid = command.gid;
+            map.groups = command.groups.clone();
+            map.process_group = command.process_group;
+            map.setsid = command.setsid;
+            map.rlimits = command.rlimits.clone();
+        }
+
+        #[cfg(target_os = "linux")]
+        {
+            map.parent_death_signal = command.parent_death_signal.map(|signal| signal.0);
+        }
+
+        map
+    }
+}
+
+/// Serializes the command as a list of strings (the program followed
+/// by its arguments) when it holds no other option, as a map
+/// otherwise.
+///
+/// Sensitive values are redacted, which makes the serialization lossy:
+/// commands holding some are serialized as a map, marking redacted
+/// arguments by index (`sensitive-args`) and redacted environment
+/// variables by key (`sensitive-envs`).
 impl Serialize for Command {
     fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
-        let len = match &self.args {
+        let map = CommandMap::from(self);
+
+        if !map.is_seq() {
+            return map.serialize(serializer);
+        }
+
+        let args = self.get_redacted_args();
+
+        let len = match &args {
             Some(args) => args.len() + 1,
             None => 0,
         };
@@ -21,7 +295,7 @@ impl Serialize for Command {
 
         seq.serialize_element(&self.program.to_str())?;
 
-        if let Some(args) = &self.args {
+        if let Some(args) = args {
             for arg in args {
                 seq.serialize_element(&arg.to_str())?;
             }
@@ -43,11 +317,13 @@ impl<'de> Visitor<'de> for CommandVisitor {
     type Value = Command;
 
     fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
-        formatter.write_str("a string (full command) or a list of string (command arguments)")
+        formatter.write_str(
+            "a string (full command), a list of string (command arguments) or a map (detailed command)",
+        )
     }
 
     fn visit_str<E: Error>(self, v: &str) -> Result<Self::Value, E> {
-        let mut args = v.split_whitespace();
+        let mut args = split(v).map_err(E::custom)?.into_iter();
         let program = args.next().ok_or(E::custom("command cannot be empty"))?;
         let mut command = Command::new(program);
         command.args(args);
@@ -67,6 +343,65 @@ impl<'de> Visitor<'de> for CommandVisitor {
 
         Ok(command)
     }
+
+    fn visit_map<A: MapAccess<'de>>(self, map: A) -> Result<Self::Value, A::Error> {
+        let map = CommandMap::deserialize(MapAccessDeserializer::new(map))?;
+        map.into_command()
+    }
+}
+
+/// Splits the given full command into words, like a POSIX shell
+/// would do without any expansion.
+///
+/// Words are separated by whitespaces, unless quoted or escaped.
+/// Single quotes preserve their content literally, double quotes
+/// preserve it except for backslashes escaping `"` and `\`. Outside
+/// of quotes, a backslash preserves the next character.
+fn split(command: &str) -> Result<Vec<String>, &'static str> {
+    let mut words = Vec::new();
+    let mut word: Option<String> = None;
+    let mut chars = command.chars();
+
+    while let Some(c) = chars.next() {
+        match c {
+            c if c.is_whitespace() => words.extend(word.take()),
+            '\'' => {
+                let word = word.get_or_insert_with(String::new);
+
+                loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some(c) => word.push(c),
+                        None => return Err("command has an unclosed single quote"),
+                    }
+                }
+            }
+            '"' => {
+                let word = word.get_or_insert_with(String::new);
+
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some(c @ ('"' | '\\')) => word.push(c),
+                            Some(c) => word.extend(['\\', c]),
+                            None => return Err("command has an unclosed double quote"),
+                        },
+                        Some(c) => word.push(c),
+                        None => return Err("command has an unclosed double quote"),
+                    }
+                }
+            }
+            '\\' => match chars.next() {
+                Some(c) => word.get_or_insert_with(String::new).push(c),
+                None => return Err("command ends with a backslash"),
+            },
+            c => word.get_or_insert_with(String::new).push(c),
+        }
+    }
+
+    words.extend(word);
+    Ok(words)
 }
 
 #[cfg(test)]
@@ -76,14 +411,54 @@ mod tests {
         Deserialize,
     };
 
-    use crate::Command;
+    use serde_json::json;
 
-    // TODO
-    //
-    // #[test]
-    // fn serialize() {
-    //
-    // }
+    #[cfg(unix)]
+    use crate::RlimitResource;
+    #[cfg(target_os = "linux")]
+    use crate::Signal;
+    use crate::{Command, EnvInherit, Redirect};
+
+    #[test]
+    fn serialize_seq() {
+        let mut command = Command::new("program");
+        command.arg("arg1").arg("arg2");
+
+        let got = serde_json::to_value(&command).unwrap();
+        assert_eq!(json!(["program", "arg1", "arg2"]), got);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn serialize_map() {
+        let mut command = Command::new("program");
+        command.arg("arg1").uid(1000).gid(100).groups(&[10, 20]);
+
+        let got = serde_json::to_value(&command).unwrap();
+        let expected = json!({
+            "program": "program",
+            "args": ["arg1"],
+            "uid": 1000,
+            "gid": 100,
+            "groups": [10, 20],
+        });
+        assert_eq!(expected, got);
+    }
+
+    #[test]
+    fn serialize_map_envs() {
+        let mut command = Command::new("program");
+        command.arg("arg1").env("KEY", "val").current_dir("/tmp");
+
+        let got = serde_json::to_value(&command).unwrap();
+        let expected = json!({
+            "program": "program",
+            "args": ["arg1"],
+            "envs": { "KEY": "val" },
+            "current-dir": "/tmp",
+        });
+        assert_eq!(expected, got);
+    }
 
     #[test]
     fn deserialize_string() {
@@ -101,6 +476,21 @@ mod tests {
         assert_eq!(expected, got);
     }
 
+    #[test]
+    fn deserialize_quoted_string() {
+        let mut expected = Command::new("program");
+        expected.args(["a b", "c \"d\"", "e f", "", "g'h"]);
+
+        let s = String::from(r#"program 'a b' "c \"d\"" e\ f "" "g'h""#);
+        let s = StringDeserializer::<Error>::new(s);
+        let got = Command::deserialize(s).unwrap();
+        assert_eq!(expected, got);
+
+        let s = StringDeserializer::<Error>::new(String::from("program 'arg"));
+        let err = Command::deserialize(s).unwrap_err();
+        assert_eq!("command has an unclosed single quote", err.to_string());
+    }
+
     #[test]
     fn deserialize_empty_string() {
         let s = StringDeserializer::<Error>::new(String::new());
@@ -130,4 +520,145 @@ mod tests {
         let err: Error = Command::deserialize(s).unwrap_err();
         assert_eq!("command cannot be empty", err.to_string());
     }
+
+    #[cfg(unix)]
+    #[test]
+    fn deserialize_map() {
+        let mut expected = Command::new("program");
+        expected.arg("arg1").uid(1000).gid(100).groups(&[10, 20]);
+
+        let s = json!({
+            "program": "program",
+            "args": ["arg1"],
+            "uid": 1000,
+            "gid": 100,
+            "groups": [10, 20],
+        });
+        let got = Command::deserialize(s).unwrap();
+        assert_eq!(expected, got);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn deserialize_rlimits() {
+        let mut expected = Command::new("program");
+        expected.rlimit(RlimitResource::Cpu, Some(10), None);
+
+        let s = json!({
+            "program": "program",
+            "rlimits": [{ "resource": "cpu", "soft": 10 }],
+        });
+        let got = Command::deserialize(s).unwrap();
+        assert_eq!(expected, got);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn deserialize_lifetime() {
+        let mut expected = Command::new("program");
+        expected.kill_on_drop(true);
+        expected.parent_death_signal(Signal::TERM);
+
+        let s = json!({
+            "program": "program",
+            "kill-on-drop": true,
+            "parent-death-signal": 15,
+        });
+        let got = Command::deserialize(s.clone()).unwrap();
+        assert_eq!(expected, got);
+        assert_eq!(s, serde_json::to_value(&got).unwrap());
+    }
+
+    #[test]
+    fn deserialize_empty_map() {
+        let s = json!({ "program": " " });
+        let err = Command::deserialize(s).unwrap_err();
+        assert_eq!("command cannot be empty", err.to_string());
+    }
+
+    #[test]
+    fn deserialize_redirects() {
+        let mut expected = Command::new("program");
+        expected.stdin_redirect(Redirect::Null);
+        expected.stdout_file("out.log", true);
+        expected.stderr_file("err.log", false);
+
+        let s = json!({
+            "program": "program",
+            "stdin": "null",
+            "stdout": { "append": "out.log" },
+            "stderr": { "file": "err.log" },
+        });
+        let got = Command::deserialize(s.clone()).unwrap();
+        assert_eq!(expected, got);
+        assert_eq!(s, serde_json::to_value(&got).unwrap());
+    }
+
+    #[test]
+    fn deserialize_env_inherit() {
+        let mut expected = Command::new("program");
+        expected.env_inherit(EnvInherit::allowlist(["PATH", "HOME"]));
+        expected.env("KEY", "val");
+
+        let s = json!({
+            "program": "program",
+            "envs": { "KEY": "val" },
+            "env-inherit": { "allowlist": ["PATH", "HOME"] },
+        });
+        let got = Command::deserialize(s.clone()).unwrap();
+        assert_eq!(expected, got);
+        assert_eq!(s, serde_json::to_value(&got).unwrap());
+
+        let s = json!({ "program": "program", "env-inherit": "none" });
+        let got = Command::deserialize(s).unwrap();
+        assert_eq!(EnvInherit::None, got.env_inherit);
+    }
+
+    #[test]
+    fn serialize_redacted() {
+        let mut command = Command::new("program");
+        command.arg("--pass").sensitive_arg("secret");
+
+        let expected = json!({
+            "program": "program",
+            "args": ["--pass", "<redacted>"],
+            "sensitive-args": [1],
+        });
+        assert_eq!(expected, serde_json::to_value(&command).unwrap());
+
+        command.sensitive_env("TOKEN", "secret");
+
+        let expected = json!({
+            "program": "program",
+            "args": ["--pass", "<redacted>"],
+            "envs": { "TOKEN": "<redacted>" },
+            "sensitive-args": [1],
+            "sensitive-envs": ["TOKEN"],
+        });
+        assert_eq!(expected, serde_json::to_value(&command).unwrap());
+    }
+
+    #[test]
+    fn deserialize_redacted() {
+        let mut command = Command::new("program");
+        command.arg("--pass").sensitive_arg("secret");
+        command.sensitive_env("TOKEN", "secret");
+
+        let value = serde_json::to_value(&command).unwrap();
+        let got = Command::deserialize(value).unwrap();
+
+        // values are lost, but stay marked as sensitive
+        let mut expected = Command::new("program");
+        expected.arg("--pass").sensitive_arg(Command::REDACTED);
+        expected.sensitive_env("TOKEN", Command::REDACTED);
+        assert_eq!(expected, got);
+
+        let s = json!({ "program": "program", "args": ["a"], "sensitive-args": [1] });
+        let err = Command::deserialize(s).unwrap_err();
+        assert_eq!("sensitive argument index out of bounds", err.to_string());
+
+        let s = json!({ "program": "program", "sensitive-envs": ["TOKEN"] });
+        let err = Command::deserialize(s).unwrap_err();
+        assert_eq!("sensitive environment variable not set", err.to_string());
+    }
 }
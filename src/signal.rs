@@ -0,0 +1,38 @@
+//! Module dedicated to Unix [`Signal`]s.
+
+use std::fmt;
+
+/// The Unix signal sent to child processes.
+///
+/// Common signals are available as associated constants. Any other
+/// signal number supported by the platform can be built using the
+/// tuple constructor.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct Signal(pub i32);
+
+impl Signal {
+    pub const HUP: Self = Self(libc::SIGHUP);
+    pub const INT: Self = Self(libc::SIGINT);
+    pub const QUIT: Self = Self(libc::SIGQUIT);
+    pub const KILL: Self = Self(libc::SIGKILL);
+    pub const USR1: Self = Self(libc::SIGUSR1);
+    pub const USR2: Self = Self(libc::SIGUSR2);
+    pub const TERM: Self = Self(libc::SIGTERM);
+}
+
+impl fmt::Display for Signal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match *self {
+            Self::HUP => "SIGHUP",
+            Self::INT => "SIGINT",
+            Self::QUIT => "SIGQUIT",
+            Self::KILL => "SIGKILL",
+            Self::USR1 => "SIGUSR1",
+            Self::USR2 => "SIGUSR2",
+            Self::TERM => "SIGTERM",
+            Self(signal) => return write!(f, "signal {signal}"),
+        };
+
+        f.write_str(name)
+    }
+}
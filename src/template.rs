@@ -0,0 +1,273 @@
+//! Module dedicated to the [`CommandTemplate`].
+
+use std::{
+    ffi::{OsStr, OsString},
+    path::PathBuf,
+};
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::{Command, TemplateError};
+
+/// The command template.
+///
+/// A template is a [`Command`] whose program, arguments, environment
+/// variable values and working directory can contain named
+/// placeholders like `{name}`, which are replaced by
+/// [`CommandTemplate::render`]. Literal braces are written `{{` and
+/// `}}`.
+///
+/// Placeholders are expanded per argument and values are never
+/// interpreted by a shell nor expanded again, so they cannot inject
+/// arguments. Templates can optionally expand a leading `~` and
+/// `$VAR` or `${VAR}` using [`CommandTemplate::render_with_env`].
+///
+/// Templates are de/serialized like [`Command`] when the `serde`
+/// feature is enabled.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize), serde(transparent))]
+pub struct CommandTemplate {
+    /// The command holding placeholders.
+    pub command: Command,
+}
+
+impl CommandTemplate {
+    /// Creates a new template from the given command builder.
+    pub fn new(command: Command) -> Self {
+        Self { command }
+    }
+
+    /// Renders the template into a concrete [`Command`], using the
+    /// given lookup to replace placeholders.
+    ///
+    /// Std{in,out,err} configurations and inherited file descriptors
+    /// of the template command are not part of the rendered command.
+    pub fn render<P>(&self, placeholders: P) -> Result<Command, TemplateError>
+    where
+        P: Fn(&str) -> Option<String>,
+    {
+        self.expand(&placeholders, None::<&fn(&str) -> Option<String>>)
+    }
+
+    /// Renders the template into a concrete [`Command`], using the
+    /// given lookups to replace placeholders and environment
+    /// variables.
+    ///
+    /// A `~` alone or followed by `/` at the beginning of a value is
+    /// replaced by the `HOME` variable. The environment lookup is
+    /// provided by the caller, for example `|key|
+    /// std::env::var(key).ok()`.
+    pub fn render_with_env<P, E>(&self, placeholders: P, env: E) -> Result<Command, TemplateError>
+    where
+        P: Fn(&str) -> Option<String>,
+        E: Fn(&str) -> Option<String>,
+    {
+        self.expand(&placeholders, Some(&env))
+    }
+
+    fn expand<P, E>(&self, placeholders: &P, env: Option<&E>) -> Result<Command, TemplateError>
+    where
+        P: Fn(&str) -> Option<String>,
+        E: Fn(&str) -> Option<String>,
+    {
+        let expand = |value: &OsStr| expand(value, placeholders, env);
+        let mut command = self.command.clone();
+
+        command.program = expand(&command.program)?;
+
+        if let Some(args) = command.args.as_mut() {
+            for arg in args {
+                *arg = expand(arg)?;
+            }
+        }
+
+        if let Some(envs) = command.envs.as_mut() {
+            for val in envs.values_mut() {
+                *val = expand(val)?;
+            }
+        }
+
+        if let Some(dir) = command.current_dir.as_mut() {
+            *dir = PathBuf::from(expand(dir.as_os_str())?);
+        }
+
+        Ok(command)
+    }
+}
+
+impl From<Command> for CommandTemplate {
+    fn from(command: Command) -> Self {
+        Self::new(command)
+    }
+}
+
+/// Expands placeholders, and optionally environment variables, of
+/// the given value.
+///
+/// Values that are not valid Unicode are left untouched.
+fn expand<P, E>(value: &OsStr, placeholders: &P, env: Option<&E>) -> Result<OsString, TemplateError>
+where
+    P: Fn(&str) -> Option<String>,
+    E: Fn(&str) -> Option<String>,
+{
+    let Some(template) = value.to_str() else {
+        return Ok(value.to_owned());
+    };
+
+    let unclosed = || TemplateError::UnclosedPlaceholder(template.to_owned());
+    let mut output = String::with_capacity(template.len());
+    let mut rest = template;
+
+    if let Some(env) = env {
+        if rest == "~" || rest.starts_with("~/") {
+            output.push_str(&lookup_var(env, "HOME")?);
+            rest = &rest[1..];
+        }
+    }
+
+    while let Some(i) = rest.find(['{', '}', '$']) {
+        output.push_str(&rest[..i]);
+        let c = &rest[i..i + 1];
+        rest = &rest[i + 1..];
+
+        match (c, env) {
+            ("{", _) if rest.starts_with('{') => {
+                output.push('{');
+                rest = &rest[1..];
+            }
+            ("}", _) if rest.starts_with('}') => {
+                output.push('}');
+                rest = &rest[1..];
+            }
+            ("{", _) => {
+                let end = rest.find('}').ok_or_else(unclosed)?;
+                let name = &rest[..end];
+                let Some(value) = placeholders(name) else {
+                    return Err(TemplateError::UnknownPlaceholder(name.to_owned()));
+                };
+                output.push_str(&value);
+                rest = &rest[end + 1..];
+            }
+            ("$", Some(env)) if rest.starts_with('{') => {
+                let end = rest.find('}').ok_or_else(unclosed)?;
+                output.push_str(&lookup_var(env, &rest[1..end])?);
+                rest = &rest[end + 1..];
+            }
+            ("$", Some(env)) => {
+                let end = rest
+                    .find(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+                    .unwrap_or(rest.len());
+
+                if end == 0 {
+                    output.push('$');
+                } else {
+                    output.push_str(&lookup_var(env, &rest[..end])?);
+                    rest = &rest[end..];
+                }
+            }
+            (c, _) => output.push_str(c),
+        }
+    }
+
+    output.push_str(rest);
+    Ok(output.into())
+}
+
+fn lookup_var<E>(env: &E, key: &str) -> Result<String, TemplateError>
+where
+    E: Fn(&str) -> Option<String>,
+{
+    env(key).ok_or_else(|| TemplateError::UndefinedVariable(key.to_owned()))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use crate::{Command, TemplateError};
+
+    use super::CommandTemplate;
+
+    fn lookup<'a>(vars: &'a [(&str, &str)]) -> impl Fn(&str) -> Option<String> + 'a {
+        let vars: HashMap<_, _> = vars.iter().copied().collect();
+        move |key| vars.get(key).map(ToString::to_string)
+    }
+
+    #[test]
+    fn render() {
+        let mut command = Command::new("notify-send");
+        command.arg("{title}").arg("{{{body}}}").arg("$HOME");
+        let template = CommandTemplate::new(command);
+
+        let placeholders = lookup(&[("title", "a b; rm -rf /"), ("body", "{title}")]);
+        let command = template.render(placeholders).unwrap();
+
+        assert_eq!("notify-send", command.program);
+        let args = command.args.unwrap();
+        assert_eq!(["a b; rm -rf /", "{{title}}", "$HOME"], args.as_slice());
+    }
+
+    #[test]
+    fn render_with_env() {
+        let mut command = Command::new("~/bin/hook");
+        command
+            .arg("$ACCOUNT")
+            .arg("${ACCOUNT}-{n}")
+            .arg("a~$")
+            .arg("~user");
+        let template = CommandTemplate::new(command);
+
+        let placeholders = lookup(&[("n", "$ACCOUNT")]);
+        let env = lookup(&[("HOME", "/home/me"), ("ACCOUNT", "work")]);
+        let command = template.render_with_env(placeholders, env).unwrap();
+
+        assert_eq!("/home/me/bin/hook", command.program);
+        let args = command.args.unwrap();
+        assert_eq!(["work", "work-$ACCOUNT", "a~$", "~user"], args.as_slice());
+    }
+
+    #[test]
+    fn render_errors() {
+        let render = |arg: &str| {
+            let mut command = Command::new("echo");
+            command.arg(arg);
+            let template = CommandTemplate::new(command);
+            template
+                .render_with_env(lookup(&[]), lookup(&[]))
+                .unwrap_err()
+        };
+
+        assert!(matches!(render("{a}"), TemplateError::UnknownPlaceholder(p) if p == "a"));
+        assert!(matches!(render("$A"), TemplateError::UndefinedVariable(v) if v == "A"));
+        assert!(matches!(render("{a"), TemplateError::UnclosedPlaceholder(t) if t == "{a"));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn deserialize() {
+        let template: CommandTemplate =
+            serde_json::from_str(r#"["notify-send", "{title}", "{body}"]"#).unwrap();
+
+        let placeholders = lookup(&[("title", "Hello"), ("body", "world")]);
+        let command = template.render(placeholders).unwrap();
+
+        let mut expected = Command::new("notify-send");
+        expected.arg("Hello").arg("world");
+        assert_eq!(expected, command);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn deserialize_string() {
+        let template: CommandTemplate =
+            serde_json::from_str(r#""notify-send \"{title}\" \"{body}\"""#).unwrap();
+
+        let placeholders = lookup(&[("title", "Hello"), ("body", "big world")]);
+        let command = template.render(placeholders).unwrap();
+
+        let mut expected = Command::new("notify-send");
+        expected.arg("Hello").arg("big world");
+        assert_eq!(expected, command);
+    }
+}